@@ -0,0 +1,68 @@
+//! Runs the VM in-process over the loops of the examples, compare value representations with
+//! ``cargo bench --bench loops`` followed by ``cargo bench --bench loops --features nan-boxing``,
+//! criterion reports the change relative to the previous run.
+//! Scripts are compiled once, so only execution is measured
+use std::{fs, io, path::Path};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use rlox::{
+    alias::{StoredChunk, StoredHeap},
+    chunk::Chunk,
+    compiler::Compiler,
+    heap::{DEFAULT_GC_THRESHOLD, Heap},
+    namespace::NameSpace,
+    natives, rc_refcell,
+    vm::VirtualMachine,
+};
+
+const EXAMPLES: [&str; 5] = ["loop", "break_continue", "for_in", "arithmetic_loop", "fib"];
+
+/// Compiled script, its globals have slots resolved but nothing defined yet
+struct Compiled {
+    chunk: StoredChunk,
+    globals: NameSpace,
+    heap: StoredHeap,
+}
+
+fn compile(path: &Path) -> Compiled {
+    let source = fs::read_to_string(path).expect("Failed to read example");
+    let chunk = rc_refcell!(Chunk::new());
+    let globals = rc_refcell!(NameSpace::from_file(path.to_path_buf()));
+    let heap = rc_refcell!(Heap::new(DEFAULT_GC_THRESHOLD, false));
+    Compiler::from_source(source, globals.clone(), heap.clone(), false)
+        .compile(chunk.clone())
+        .expect("Failed to compile example");
+    let globals = globals.borrow().clone();
+    Compiled {
+        chunk,
+        globals,
+        heap,
+    }
+}
+
+fn run(compiled: &Compiled) {
+    let globals = rc_refcell!(compiled.globals.clone());
+    let mut vm = VirtualMachine::new(
+        compiled.chunk.clone(),
+        globals,
+        compiled.heap.clone(),
+        false,
+    );
+    natives::define_builtins(&mut vm);
+    vm.set_output(io::sink());
+    vm.exec().expect("Example failed");
+}
+
+fn loops(c: &mut Criterion) {
+    let examples_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut group = c.benchmark_group("loops");
+    group.sample_size(10);
+    for example in EXAMPLES {
+        let compiled = compile(&examples_dir.join(format!("{example}.lox")));
+        group.bench_function(example, |b| b.iter(|| run(&compiled)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, loops);
+criterion_main!(benches);
@@ -2,9 +2,11 @@ use std::{cell::RefCell, rc::Rc};
 
 use anyhow::Error;
 
-use crate::{chunk::Chunk, object::Object, value::Value};
+use crate::{chunk::Chunk, heap::Heap, namespace::NameSpace, object::{closure::Upvalue, Object}};
 
-pub type StoredValue = Rc<RefCell<Value>>;
 pub type StoredChunk = Rc<RefCell<Chunk>>;
 pub type VoidResult = Result<(), Error>;
 pub type DynObject = Box<dyn Object>;
+pub type StoredUpvalue = Rc<RefCell<Upvalue>>;
+pub type StoredNameSpace = Rc<RefCell<NameSpace>>;
+pub type StoredHeap = Rc<RefCell<Heap>>;
@@ -1,10 +1,178 @@
 use std::fmt::Display;
 
+use crate::{errors::RuntimeErrorKind, object::ResultRE, value::Value};
+
 pub enum BinOpKind {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
+    Pow,
+    FloorDiv,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+/// Integer operand of bitwise operator, floats are accepted only without fractional part
+pub fn integral_operand(value: &Value) -> ResultRE<i64> {
+    value.as_int().ok_or_else(|| RuntimeErrorKind::TypeError {
+        expected: "integral operand".to_owned(),
+        provided: match value {
+            Value::Float(_) => format!("float {value}"),
+            _ => value.type_name(),
+        },
+    })
+}
+
+impl BinOpKind {
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            BinOpKind::BitAnd
+                | BinOpKind::BitOr
+                | BinOpKind::BitXor
+                | BinOpKind::ShiftLeft
+                | BinOpKind::ShiftRight
+        )
+    }
+
+    /// Bitwise operators, shifting out significant bits is reported as overflow
+    pub fn apply_bitwise(&self, a: i64, b: i64) -> ResultRE<Value> {
+        if matches!(self, BinOpKind::ShiftLeft | BinOpKind::ShiftRight) && b < 0 {
+            return Err(RuntimeErrorKind::InvalidValue {
+                message: format!("negative shift count {b}"),
+            });
+        }
+        let result = match self {
+            BinOpKind::BitAnd => a & b,
+            BinOpKind::BitOr => a | b,
+            BinOpKind::BitXor => a ^ b,
+            BinOpKind::ShiftLeft => {
+                let shifted = if b < 64 { a << b } else { 0 };
+                if b >= 64 && a != 0 || shifted >> b.min(63) != a {
+                    return Err(RuntimeErrorKind::IntegerOverflow {
+                        expression: format!("{a} {self} {b}"),
+                    });
+                }
+                shifted
+            }
+            // arithmetic shift, sign is kept
+            BinOpKind::ShiftRight => a >> b.min(63),
+            _ => unreachable!(),
+        };
+        Ok(Value::Int(result))
+    }
+
+    /// Exact integer arithmetic, overflow is reported instead of wrapping
+    pub fn apply_int(&self, a: i64, b: i64) -> ResultRE<Value> {
+        let zero_division = || RuntimeErrorKind::ZeroDivision {
+            op: self.to_string(),
+        };
+        let result = match self {
+            BinOpKind::Add => a.checked_add(b),
+            BinOpKind::Sub => a.checked_sub(b),
+            BinOpKind::Mul => a.checked_mul(b),
+            BinOpKind::Div => {
+                // true division, 7 / 2 is 3.5, use ~/ for integer result
+                return self.apply_float(a as f64, b as f64).map(Value::Float);
+            }
+            BinOpKind::Mod => {
+                if b == 0 {
+                    return Err(zero_division());
+                }
+                a.checked_rem(b).map(|rem| {
+                    if rem != 0 && (rem < 0) != (b < 0) {
+                        rem + b
+                    } else {
+                        rem
+                    }
+                })
+            }
+            BinOpKind::FloorDiv => {
+                if b == 0 {
+                    return Err(zero_division());
+                }
+                a.checked_div(b).map(|quotient| {
+                    if a % b != 0 && (a < 0) != (b < 0) {
+                        quotient - 1
+                    } else {
+                        quotient
+                    }
+                })
+            }
+            BinOpKind::Pow => {
+                if b < 0 {
+                    return self.apply_float(a as f64, b as f64).map(Value::Float);
+                }
+                u32::try_from(b).ok().and_then(|exp| a.checked_pow(exp))
+            }
+            _ => return self.apply_bitwise(a, b),
+        };
+
+        result
+            .map(Value::Int)
+            .ok_or_else(|| RuntimeErrorKind::IntegerOverflow {
+                expression: format!("{a} {self} {b}"),
+            })
+    }
+
+    /// Float arithmetic, operations that would give inf or NaN are reported as errors
+    pub fn apply_float(&self, a: f64, b: f64) -> ResultRE<f64> {
+        self.check_float_operands(a, b)?;
+        let result = match self {
+            BinOpKind::Add => a + b,
+            BinOpKind::Sub => a - b,
+            BinOpKind::Mul => a * b,
+            BinOpKind::Div => a / b,
+            // floored modulo, result takes sign of the divisor
+            BinOpKind::Mod => {
+                let rem = a % b;
+                if rem != 0.0 && (rem < 0.0) != (b < 0.0) {
+                    rem + b
+                } else {
+                    rem
+                }
+            }
+            BinOpKind::Pow => a.powf(b),
+            BinOpKind::FloorDiv => (a / b).floor(),
+            _ => unreachable!(),
+        };
+        self.check_float_result(a, b, result)
+    }
+
+    /// Rejects operands for which result would silently become inf or NaN
+    fn check_float_operands(&self, a: f64, b: f64) -> ResultRE<()> {
+        match self {
+            BinOpKind::Div | BinOpKind::Mod | BinOpKind::FloorDiv if b == 0.0 => {
+                Err(RuntimeErrorKind::ZeroDivision {
+                    op: self.to_string(),
+                })
+            }
+            BinOpKind::Pow if a == 0.0 && b < 0.0 => Err(RuntimeErrorKind::ZeroDivision {
+                op: self.to_string(),
+            }),
+            BinOpKind::Pow if a < 0.0 && b.fract() != 0.0 => Err(RuntimeErrorKind::InvalidValue {
+                message: format!("{a} ** {b} is not a real number"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects exponent result that became inf although both operands were finite
+    fn check_float_result(&self, a: f64, b: f64, result: f64) -> ResultRE<f64> {
+        match self {
+            BinOpKind::Pow if !result.is_finite() && a.is_finite() && b.is_finite() => {
+                Err(RuntimeErrorKind::FloatOverflow {
+                    expression: format!("{} {self} {}", Value::Float(a), Value::Float(b)),
+                })
+            }
+            _ => Ok(result),
+        }
+    }
 }
 
 impl Display for BinOpKind {
@@ -14,6 +182,14 @@ impl Display for BinOpKind {
             BinOpKind::Sub => "-",
             BinOpKind::Mul => "*",
             BinOpKind::Div => "/",
+            BinOpKind::Mod => "%",
+            BinOpKind::Pow => "**",
+            BinOpKind::FloorDiv => "~/",
+            BinOpKind::BitAnd => "&",
+            BinOpKind::BitOr => "|",
+            BinOpKind::BitXor => "^",
+            BinOpKind::ShiftLeft => "<<",
+            BinOpKind::ShiftRight => ">>",
         };
         write!(f, "{c}")
     }
@@ -1,10 +1,14 @@
 use std::{fmt::Display, vec};
 
-use crate::alias::StoredValue;
+use strum::EnumCount;
+use strum_macros::{EnumCount, FromRepr};
+
+use crate::value::Value;
 
 const STACK_CAPACITY: usize = 256;
 
-#[derive(Debug, Clone)]
+/// Decoded instruction, chunks store it encoded by ``Chunk::push``
+#[derive(Debug, Clone, Copy)]
 pub enum OpCodeKind {
     Const { const_idx: usize },
     Negate,
@@ -12,6 +16,15 @@ pub enum OpCodeKind {
     Sub,
     Mul,
     Div,
+    Mod,
+    Pow,
+    FloorDiv,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    BitNot,
     Null,
     True,
     False,
@@ -21,14 +34,42 @@ pub enum OpCodeKind {
     Lt,
     Print,
     Pop,
-    DefineGlobal { name_idx: usize },
-    ReadGlobal { name_idx: usize },
-    SetGlobal { name_idx: usize },
+    DefineGlobal { slot: usize },
+    DefineConst { slot: usize },
+    ReadGlobal { slot: usize },
+    SetGlobal { slot: usize },
     ReadLocal { name_idx: usize },
     SetLocal { name_idx: usize },
     JumpIfFalse { offset: usize },
     Jump { offset: usize },
-    Loop { offset: usize},
+    Loop { offset: usize },
+    Call { args_count: usize },
+    Return,
+    Closure { const_idx: usize },
+    ReadUpvalue { upvalue_idx: usize },
+    SetUpvalue { upvalue_idx: usize },
+    CloseUpvalue,
+    Class { name_idx: usize },
+    Method { name_idx: usize },
+    GetProperty { name_idx: usize },
+    SetProperty { name_idx: usize },
+    Inherit,
+    GetSuper { name_idx: usize },
+    BuildList { items_count: usize },
+    GetIndex,
+    SetIndex,
+    BuildMap { entries_count: usize },
+    Contains,
+    BuildString { parts_count: usize },
+    PushHandler { offset: usize, depth: usize },
+    PopHandler,
+    Throw,
+    EndFinally,
+    Unwind { depth: usize },
+    GetIterator,
+    ForIter { slot: usize, offset: usize },
+    Import { path_idx: usize },
+    Export { name_idx: usize },
 }
 
 impl Display for OpCodeKind {
@@ -40,6 +81,15 @@ impl Display for OpCodeKind {
             OpCodeKind::Sub => ("OP_SUB", "".to_string()),
             OpCodeKind::Mul => ("OP_MUL", "".to_string()),
             OpCodeKind::Div => ("OP_DIV", "".to_string()),
+            OpCodeKind::Mod => ("OP_MOD", "".to_string()),
+            OpCodeKind::Pow => ("OP_POW", "".to_string()),
+            OpCodeKind::FloorDiv => ("OP_FLOOR_DIV", "".to_string()),
+            OpCodeKind::BitAnd => ("OP_BIT_AND", "".to_string()),
+            OpCodeKind::BitOr => ("OP_BIT_OR", "".to_string()),
+            OpCodeKind::BitXor => ("OP_BIT_XOR", "".to_string()),
+            OpCodeKind::ShiftLeft => ("OP_SHIFT_LEFT", "".to_string()),
+            OpCodeKind::ShiftRight => ("OP_SHIFT_RIGHT", "".to_string()),
+            OpCodeKind::BitNot => ("OP_BIT_NOT", "".to_string()),
             OpCodeKind::Null => ("OP_NULL", "".to_string()),
             OpCodeKind::False => ("OP_FALSE", "".to_string()),
             OpCodeKind::True => ("OP_TRUE", "".to_string()),
@@ -49,94 +99,417 @@ impl Display for OpCodeKind {
             OpCodeKind::Lt => ("OP_LT", "".to_string()),
             OpCodeKind::Print => ("OP_PRINT", "".to_string()),
             OpCodeKind::Pop => ("OP_POP", "".to_string()),
-            OpCodeKind::DefineGlobal { name_idx } => ("OP_DEFINE_GLOBAL", format!("{name_idx}")),
-            OpCodeKind::ReadGlobal { name_idx } => ("OP_READ_GLOBAL", format!("{name_idx}")),
-            OpCodeKind::SetGlobal { name_idx } => ("OP_SET_GLOBAL", format!("{name_idx}")),
+            OpCodeKind::DefineGlobal { slot } => ("OP_DEFINE_GLOBAL", format!("{slot}")),
+            OpCodeKind::DefineConst { slot } => ("OP_DEFINE_CONST", format!("{slot}")),
+            OpCodeKind::ReadGlobal { slot } => ("OP_READ_GLOBAL", format!("{slot}")),
+            OpCodeKind::SetGlobal { slot } => ("OP_SET_GLOBAL", format!("{slot}")),
             OpCodeKind::ReadLocal { name_idx } => ("OP_READ_LOCAL", format!("{name_idx}")),
             OpCodeKind::SetLocal { name_idx } => ("OP_SET_LOCAL", format!("{name_idx}")),
             OpCodeKind::JumpIfFalse { offset } => ("OP_JUMP_IF_FALSE", format!("{offset}")),
             OpCodeKind::Jump { offset } => ("OP_JUMP", format!("{offset}")),
             OpCodeKind::Loop { offset } => ("OP_LOOP", format!("{offset}")),
+            OpCodeKind::Call { args_count } => ("OP_CALL", format!("{args_count}")),
+            OpCodeKind::Return => ("OP_RETURN", "".to_string()),
+            OpCodeKind::Closure { const_idx } => ("OP_CLOSURE", format!("{const_idx}")),
+            OpCodeKind::ReadUpvalue { upvalue_idx } => ("OP_READ_UPVALUE", format!("{upvalue_idx}")),
+            OpCodeKind::SetUpvalue { upvalue_idx } => ("OP_SET_UPVALUE", format!("{upvalue_idx}")),
+            OpCodeKind::CloseUpvalue => ("OP_CLOSE_UPVALUE", "".to_string()),
+            OpCodeKind::Class { name_idx } => ("OP_CLASS", format!("{name_idx}")),
+            OpCodeKind::Method { name_idx } => ("OP_METHOD", format!("{name_idx}")),
+            OpCodeKind::GetProperty { name_idx } => ("OP_GET_PROPERTY", format!("{name_idx}")),
+            OpCodeKind::SetProperty { name_idx } => ("OP_SET_PROPERTY", format!("{name_idx}")),
+            OpCodeKind::Inherit => ("OP_INHERIT", "".to_string()),
+            OpCodeKind::GetSuper { name_idx } => ("OP_GET_SUPER", format!("{name_idx}")),
+            OpCodeKind::BuildList { items_count } => ("OP_BUILD_LIST", format!("{items_count}")),
+            OpCodeKind::GetIndex => ("OP_GET_INDEX", "".to_string()),
+            OpCodeKind::SetIndex => ("OP_SET_INDEX", "".to_string()),
+            OpCodeKind::BuildMap { entries_count } => ("OP_BUILD_MAP", format!("{entries_count}")),
+            OpCodeKind::Contains => ("OP_CONTAINS", "".to_string()),
+            OpCodeKind::BuildString { parts_count } => ("OP_BUILD_STRING", format!("{parts_count}")),
+            OpCodeKind::PushHandler { offset, depth } => {
+                ("OP_PUSH_HANDLER", format!("{offset} {depth}"))
+            }
+            OpCodeKind::PopHandler => ("OP_POP_HANDLER", "".to_string()),
+            OpCodeKind::Throw => ("OP_THROW", "".to_string()),
+            OpCodeKind::EndFinally => ("OP_END_FINALLY", "".to_string()),
+            OpCodeKind::Unwind { depth } => ("OP_UNWIND", format!("{depth}")),
+            OpCodeKind::GetIterator => ("OP_GET_ITERATOR", "".to_string()),
+            OpCodeKind::ForIter { slot, offset } => ("OP_FOR_ITER", format!("{slot} {offset}")),
+            OpCodeKind::Import { path_idx } => ("OP_IMPORT", format!("{path_idx}")),
+            OpCodeKind::Export { name_idx } => ("OP_EXPORT", format!("{name_idx}")),
         };
 
         write!(f, "{name:<12} {args:<6}")
     }
 }
 
-#[derive(Debug)]
-pub struct OpCode {
-    kind: OpCodeKind,
-    line: usize,
+/// Opcode byte, its two high bits hold the width of the index operands
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, FromRepr, EnumCount)]
+enum OpCode {
+    Const,
+    Negate,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    FloorDiv,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    BitNot,
+    Null,
+    True,
+    False,
+    Not,
+    Eq,
+    Gt,
+    Lt,
+    Print,
+    Pop,
+    DefineGlobal,
+    DefineConst,
+    ReadGlobal,
+    SetGlobal,
+    ReadLocal,
+    SetLocal,
+    JumpIfFalse,
+    Jump,
+    Loop,
+    Call,
+    Return,
+    Closure,
+    ReadUpvalue,
+    SetUpvalue,
+    CloseUpvalue,
+    Class,
+    Method,
+    GetProperty,
+    SetProperty,
+    Inherit,
+    GetSuper,
+    BuildList,
+    GetIndex,
+    SetIndex,
+    BuildMap,
+    Contains,
+    BuildString,
+    PushHandler,
+    PopHandler,
+    Throw,
+    EndFinally,
+    Unwind,
+    GetIterator,
+    ForIter,
+    Import,
+    Export,
 }
 
-impl Display for OpCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} L{}", self.kind, self.line())
+const WIDTH_SHIFT: u32 = 6;
+const OP_CODE_MASK: u8 = (1 << WIDTH_SHIFT) - 1;
+const _: () = assert!(OpCode::COUNT <= OP_CODE_MASK as usize + 1);
+
+/// Largest index operand, constants, slots and counts take 1, 2 or 3 bytes
+pub const MAX_INDEX: usize = (1 << 24) - 1;
+/// Largest jump offset, jumps are patched after emission so their operand has fixed width
+pub const MAX_JUMP: usize = u16::MAX as usize;
+const JUMP_WIDTH: usize = 2;
+/// Size of an instruction holding only a jump offset, ``OP_LOOP`` is emitted after its target
+pub const JUMP_SIZE: usize = 1 + JUMP_WIDTH;
+
+/// Operands of an instruction in encoding order, the jump offset always follows the opcode byte
+enum Operand {
+    Jump(usize),
+    Index(usize),
+}
+
+fn encode(kind: OpCodeKind) -> (OpCode, Vec<Operand>) {
+    use Operand::{Index, Jump};
+
+    match kind {
+        OpCodeKind::Const { const_idx } => (OpCode::Const, vec![Index(const_idx)]),
+        OpCodeKind::Negate => (OpCode::Negate, vec![]),
+        OpCodeKind::Add => (OpCode::Add, vec![]),
+        OpCodeKind::Sub => (OpCode::Sub, vec![]),
+        OpCodeKind::Mul => (OpCode::Mul, vec![]),
+        OpCodeKind::Div => (OpCode::Div, vec![]),
+        OpCodeKind::Mod => (OpCode::Mod, vec![]),
+        OpCodeKind::Pow => (OpCode::Pow, vec![]),
+        OpCodeKind::FloorDiv => (OpCode::FloorDiv, vec![]),
+        OpCodeKind::BitAnd => (OpCode::BitAnd, vec![]),
+        OpCodeKind::BitOr => (OpCode::BitOr, vec![]),
+        OpCodeKind::BitXor => (OpCode::BitXor, vec![]),
+        OpCodeKind::ShiftLeft => (OpCode::ShiftLeft, vec![]),
+        OpCodeKind::ShiftRight => (OpCode::ShiftRight, vec![]),
+        OpCodeKind::BitNot => (OpCode::BitNot, vec![]),
+        OpCodeKind::Null => (OpCode::Null, vec![]),
+        OpCodeKind::True => (OpCode::True, vec![]),
+        OpCodeKind::False => (OpCode::False, vec![]),
+        OpCodeKind::Not => (OpCode::Not, vec![]),
+        OpCodeKind::Eq => (OpCode::Eq, vec![]),
+        OpCodeKind::Gt => (OpCode::Gt, vec![]),
+        OpCodeKind::Lt => (OpCode::Lt, vec![]),
+        OpCodeKind::Print => (OpCode::Print, vec![]),
+        OpCodeKind::Pop => (OpCode::Pop, vec![]),
+        OpCodeKind::DefineGlobal { slot } => (OpCode::DefineGlobal, vec![Index(slot)]),
+        OpCodeKind::DefineConst { slot } => (OpCode::DefineConst, vec![Index(slot)]),
+        OpCodeKind::ReadGlobal { slot } => (OpCode::ReadGlobal, vec![Index(slot)]),
+        OpCodeKind::SetGlobal { slot } => (OpCode::SetGlobal, vec![Index(slot)]),
+        OpCodeKind::ReadLocal { name_idx } => (OpCode::ReadLocal, vec![Index(name_idx)]),
+        OpCodeKind::SetLocal { name_idx } => (OpCode::SetLocal, vec![Index(name_idx)]),
+        OpCodeKind::JumpIfFalse { offset } => (OpCode::JumpIfFalse, vec![Jump(offset)]),
+        OpCodeKind::Jump { offset } => (OpCode::Jump, vec![Jump(offset)]),
+        OpCodeKind::Loop { offset } => (OpCode::Loop, vec![Jump(offset)]),
+        OpCodeKind::Call { args_count } => (OpCode::Call, vec![Index(args_count)]),
+        OpCodeKind::Return => (OpCode::Return, vec![]),
+        OpCodeKind::Closure { const_idx } => (OpCode::Closure, vec![Index(const_idx)]),
+        OpCodeKind::ReadUpvalue { upvalue_idx } => (OpCode::ReadUpvalue, vec![Index(upvalue_idx)]),
+        OpCodeKind::SetUpvalue { upvalue_idx } => (OpCode::SetUpvalue, vec![Index(upvalue_idx)]),
+        OpCodeKind::CloseUpvalue => (OpCode::CloseUpvalue, vec![]),
+        OpCodeKind::Class { name_idx } => (OpCode::Class, vec![Index(name_idx)]),
+        OpCodeKind::Method { name_idx } => (OpCode::Method, vec![Index(name_idx)]),
+        OpCodeKind::GetProperty { name_idx } => (OpCode::GetProperty, vec![Index(name_idx)]),
+        OpCodeKind::SetProperty { name_idx } => (OpCode::SetProperty, vec![Index(name_idx)]),
+        OpCodeKind::Inherit => (OpCode::Inherit, vec![]),
+        OpCodeKind::GetSuper { name_idx } => (OpCode::GetSuper, vec![Index(name_idx)]),
+        OpCodeKind::BuildList { items_count } => (OpCode::BuildList, vec![Index(items_count)]),
+        OpCodeKind::GetIndex => (OpCode::GetIndex, vec![]),
+        OpCodeKind::SetIndex => (OpCode::SetIndex, vec![]),
+        OpCodeKind::BuildMap { entries_count } => (OpCode::BuildMap, vec![Index(entries_count)]),
+        OpCodeKind::Contains => (OpCode::Contains, vec![]),
+        OpCodeKind::BuildString { parts_count } => (OpCode::BuildString, vec![Index(parts_count)]),
+        OpCodeKind::PushHandler { offset, depth } => {
+            (OpCode::PushHandler, vec![Jump(offset), Index(depth)])
+        }
+        OpCodeKind::PopHandler => (OpCode::PopHandler, vec![]),
+        OpCodeKind::Throw => (OpCode::Throw, vec![]),
+        OpCodeKind::EndFinally => (OpCode::EndFinally, vec![]),
+        OpCodeKind::Unwind { depth } => (OpCode::Unwind, vec![Index(depth)]),
+        OpCodeKind::GetIterator => (OpCode::GetIterator, vec![]),
+        OpCodeKind::ForIter { slot, offset } => (OpCode::ForIter, vec![Jump(offset), Index(slot)]),
+        OpCodeKind::Import { path_idx } => (OpCode::Import, vec![Index(path_idx)]),
+        OpCodeKind::Export { name_idx } => (OpCode::Export, vec![Index(name_idx)]),
     }
 }
 
-impl OpCode {
-    pub fn new(kind: OpCodeKind, line: usize) -> Self {
-        Self { kind, line }
+/// Reads operands of one instruction, index operands have the width stored in the opcode byte
+struct Decoder<'a> {
+    code: &'a [u8],
+    offset: usize,
+    width: usize,
+}
+
+impl Decoder<'_> {
+    #[inline]
+    fn read(&mut self, width: usize) -> usize {
+        let bytes = &self.code[self.offset..self.offset + width];
+        self.offset += width;
+        match bytes {
+            [byte] => *byte as usize,
+            _ => bytes.iter().fold(0, |value, byte| value << 8 | *byte as usize),
+        }
     }
-    pub fn line(&self) -> usize {
-        self.line
+
+    fn index(&mut self) -> usize {
+        self.read(self.width)
     }
-    pub fn kind(&self) -> &OpCodeKind {
-        &self.kind
+
+    fn jump(&mut self) -> usize {
+        self.read(JUMP_WIDTH)
     }
+
+    #[inline]
+    fn decode(&mut self, op_code: OpCode) -> OpCodeKind {
+        match op_code {
+            OpCode::Const => OpCodeKind::Const { const_idx: self.index() },
+            OpCode::Negate => OpCodeKind::Negate,
+            OpCode::Add => OpCodeKind::Add,
+            OpCode::Sub => OpCodeKind::Sub,
+            OpCode::Mul => OpCodeKind::Mul,
+            OpCode::Div => OpCodeKind::Div,
+            OpCode::Mod => OpCodeKind::Mod,
+            OpCode::Pow => OpCodeKind::Pow,
+            OpCode::FloorDiv => OpCodeKind::FloorDiv,
+            OpCode::BitAnd => OpCodeKind::BitAnd,
+            OpCode::BitOr => OpCodeKind::BitOr,
+            OpCode::BitXor => OpCodeKind::BitXor,
+            OpCode::ShiftLeft => OpCodeKind::ShiftLeft,
+            OpCode::ShiftRight => OpCodeKind::ShiftRight,
+            OpCode::BitNot => OpCodeKind::BitNot,
+            OpCode::Null => OpCodeKind::Null,
+            OpCode::True => OpCodeKind::True,
+            OpCode::False => OpCodeKind::False,
+            OpCode::Not => OpCodeKind::Not,
+            OpCode::Eq => OpCodeKind::Eq,
+            OpCode::Gt => OpCodeKind::Gt,
+            OpCode::Lt => OpCodeKind::Lt,
+            OpCode::Print => OpCodeKind::Print,
+            OpCode::Pop => OpCodeKind::Pop,
+            OpCode::DefineGlobal => OpCodeKind::DefineGlobal { slot: self.index() },
+            OpCode::DefineConst => OpCodeKind::DefineConst { slot: self.index() },
+            OpCode::ReadGlobal => OpCodeKind::ReadGlobal { slot: self.index() },
+            OpCode::SetGlobal => OpCodeKind::SetGlobal { slot: self.index() },
+            OpCode::ReadLocal => OpCodeKind::ReadLocal { name_idx: self.index() },
+            OpCode::SetLocal => OpCodeKind::SetLocal { name_idx: self.index() },
+            OpCode::JumpIfFalse => OpCodeKind::JumpIfFalse { offset: self.jump() },
+            OpCode::Jump => OpCodeKind::Jump { offset: self.jump() },
+            OpCode::Loop => OpCodeKind::Loop { offset: self.jump() },
+            OpCode::Call => OpCodeKind::Call { args_count: self.index() },
+            OpCode::Return => OpCodeKind::Return,
+            OpCode::Closure => OpCodeKind::Closure { const_idx: self.index() },
+            OpCode::ReadUpvalue => OpCodeKind::ReadUpvalue { upvalue_idx: self.index() },
+            OpCode::SetUpvalue => OpCodeKind::SetUpvalue { upvalue_idx: self.index() },
+            OpCode::CloseUpvalue => OpCodeKind::CloseUpvalue,
+            OpCode::Class => OpCodeKind::Class { name_idx: self.index() },
+            OpCode::Method => OpCodeKind::Method { name_idx: self.index() },
+            OpCode::GetProperty => OpCodeKind::GetProperty { name_idx: self.index() },
+            OpCode::SetProperty => OpCodeKind::SetProperty { name_idx: self.index() },
+            OpCode::Inherit => OpCodeKind::Inherit,
+            OpCode::GetSuper => OpCodeKind::GetSuper { name_idx: self.index() },
+            OpCode::BuildList => OpCodeKind::BuildList { items_count: self.index() },
+            OpCode::GetIndex => OpCodeKind::GetIndex,
+            OpCode::SetIndex => OpCodeKind::SetIndex,
+            OpCode::BuildMap => OpCodeKind::BuildMap { entries_count: self.index() },
+            OpCode::Contains => OpCodeKind::Contains,
+            OpCode::BuildString => OpCodeKind::BuildString { parts_count: self.index() },
+            OpCode::PushHandler => OpCodeKind::PushHandler {
+                offset: self.jump(),
+                depth: self.index(),
+            },
+            OpCode::PopHandler => OpCodeKind::PopHandler,
+            OpCode::Throw => OpCodeKind::Throw,
+            OpCode::EndFinally => OpCodeKind::EndFinally,
+            OpCode::Unwind => OpCodeKind::Unwind { depth: self.index() },
+            OpCode::GetIterator => OpCodeKind::GetIterator,
+            OpCode::ForIter => {
+                let offset = self.jump();
+                OpCodeKind::ForIter { slot: self.index(), offset }
+            }
+            OpCode::Import => OpCodeKind::Import { path_idx: self.index() },
+            OpCode::Export => OpCodeKind::Export { name_idx: self.index() },
+        }
+    }
+}
+
+/// Source line of consecutive bytes of code, ``end`` is the offset after the last of them
+#[derive(Debug)]
+struct LineRun {
+    line: usize,
+    end: usize,
 }
 
+/// Bytecode of a function, instructions are encoded into bytes and decoded on execution
 #[derive(Debug)]
 pub struct Chunk {
-    code: Vec<OpCode>,
-    pub constants: Vec<StoredValue>,
+    code: Vec<u8>,
+    lines: Vec<LineRun>,
+    pub constants: Vec<Value>,
 }
 
 impl Chunk {
     pub fn new() -> Self {
         Self {
             code: vec![],
+            lines: vec![],
             constants: Vec::with_capacity(STACK_CAPACITY),
         }
     }
 
-    pub fn push(&mut self, op_code: OpCode) {
-        self.code.push(op_code);
+    /// Encodes instruction, index operands take the fewest bytes fitting the largest of them
+    pub fn push(&mut self, kind: OpCodeKind, line: usize) {
+        let (op_code, operands) = encode(kind);
+        let largest = operands.iter().fold(0, |largest, operand| match operand {
+            Operand::Index(value) => largest.max(*value),
+            Operand::Jump(_) => largest,
+        });
+        assert!(largest <= MAX_INDEX, "Operand {largest} does not fit into 24 bits");
+        let width = match largest {
+            0..=0xff => 1,
+            0x100..=0xffff => 2,
+            _ => 3,
+        };
+
+        self.code.push(((width as u8 - 1) << WIDTH_SHIFT) | op_code as u8);
+        for operand in operands {
+            let (value, width) = match operand {
+                Operand::Jump(value) => (value, JUMP_WIDTH),
+                Operand::Index(value) => (value, width),
+            };
+            self.code.extend_from_slice(&value.to_be_bytes()[usize::BITS as usize / 8 - width..]);
+        }
+
+        match self.lines.last_mut() {
+            Some(run) if run.line == line => run.end = self.code.len(),
+            _ => self.lines.push(LineRun {
+                line,
+                end: self.code.len(),
+            }),
+        }
     }
 
-    pub fn push_const(&mut self, value: StoredValue) -> usize {
+    pub fn push_const(&mut self, value: Value) -> usize {
         self.constants.push(value);
         self.constants.len() - 1 // index of const
     }
 
-    pub fn get(&self, index: usize) -> Option<&OpCode> {
-        self.code.get(index)
+    /// Instruction at ``offset`` together with the offset of the next one
+    #[inline]
+    pub fn decode(&self, offset: usize) -> Option<(OpCodeKind, usize)> {
+        let byte = *self.code.get(offset)?;
+        let op_code = OpCode::from_repr(byte & OP_CODE_MASK).expect("Invalid opcode");
+        let mut decoder = Decoder {
+            code: &self.code,
+            offset: offset + 1,
+            width: (byte >> WIDTH_SHIFT) as usize + 1,
+        };
+        let kind = decoder.decode(op_code);
+        Some((kind, decoder.offset))
     }
 
-    pub fn get_const(&self, index: usize) -> Option<&StoredValue> {
+    pub fn get_const(&self, index: usize) -> Option<&Value> {
         self.constants.get(index)
     }
 
-    pub fn replace(&mut self, index: usize, op_code: OpCode) {
-        self.code[index] = op_code;
+    /// Sets offset of the jump instruction at ``offset``, it must fit into ``MAX_JUMP``
+    pub fn patch_jump(&mut self, offset: usize, jump: usize) {
+        let bytes = (jump as u16).to_be_bytes();
+        self.code[offset + 1..offset + 1 + JUMP_WIDTH].copy_from_slice(&bytes);
+    }
+
+    /// Source line of the instruction covering byte at ``offset``
+    pub fn line(&self, offset: usize) -> usize {
+        let run_idx = self.lines.partition_point(|run| run.end <= offset);
+        self.lines[run_idx].line
     }
 
     pub fn len(&self) -> usize {
         self.code.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut iter = self.code.iter().peekable();
         let mut offset = 0;
-        while let Some(op_code) = iter.next() {
-            write!(f, "{offset}   {op_code}")?;
-            if iter.peek().is_some() {
+        while let Some((kind, next)) = self.decode(offset) {
+            write!(f, "{offset:04}   {kind} L{}", self.line(offset))?;
+            if next < self.len() {
                 writeln!(f)?;
-                offset += 1;
             }
+            offset = next;
         }
         Ok(())
     }
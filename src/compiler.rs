@@ -1,12 +1,11 @@
 use std::rc::Rc;
 
 use crate::{
-    alias::{StoredChunk, StoredValue, VoidResult},
-    chunk::{OpCode, OpCodeKind},
+    alias::{StoredChunk, StoredHeap, StoredNameSpace, VoidResult},
+    chunk::{JUMP_SIZE, MAX_INDEX, MAX_JUMP, OpCodeKind},
     errors::ParsingError,
-    object::string::StringObject,
+    object::function::{FunctionObject, UpvalueDescriptor},
     parser::Parser,
-    rc_refcell,
     scanner::Scanner,
     token::{Literal, Token, TokenType},
     value::Value,
@@ -15,11 +14,16 @@ use crate::{
 use anyhow::Error;
 use strum_macros::FromRepr;
 
+const MAX_ARGS: usize = 255;
+const MAX_UPVALUES: usize = 256;
+
 #[derive(Debug)]
 struct Local {
     name: Rc<Token>,
     depth: usize,
     pub is_initialized: bool,
+    pub is_captured: bool,
+    pub is_const: bool,
 }
 
 impl Local {
@@ -28,6 +32,8 @@ impl Local {
             name,
             depth,
             is_initialized,
+            is_captured: false,
+            is_const: false,
         }
     }
 
@@ -36,13 +42,83 @@ impl Local {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// Class being compiled, used to check ``this`` and ``super`` usage
+struct ClassScope {
+    has_superclass: bool,
+}
+
+/// Loop being compiled, collects ``break`` jumps until the loop end is known
+struct LoopScope {
+    continue_target: usize,
+    scope_depth: usize,
+    handlers_count: usize,
+    break_jumps: Vec<usize>,
+}
+
+/// Statement leaving a protected block early, it is repeated after the pending ``finally`` runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Exit {
+    Break,
+    Continue,
+    Return,
+}
+
+impl Exit {
+    /// Rethrow flag of the finally block, ``false`` and ``true`` are taken by normal exit and rethrow
+    fn flag(self) -> i64 {
+        match self {
+            Exit::Break => 2,
+            Exit::Continue => 3,
+            Exit::Return => 4,
+        }
+    }
+}
+
+/// Block protected by an exception handler, collects early exits jumping to its ``finally``
+struct HandlerScope {
+    depth: usize,
+    exits: Vec<(Exit, usize)>,
+}
+
+/// State of the function being compiled, nested declarations push a new one
+struct FunctionScope {
+    function: FunctionObject,
+    kind: FunctionKind,
+    scope_depth: usize,
+    locals: Vec<Local>,
+    loops: Vec<LoopScope>,
+    handlers: Vec<HandlerScope>,
+}
+
+impl FunctionScope {
+    pub fn new(function: FunctionObject, kind: FunctionKind) -> Self {
+        Self {
+            function,
+            kind,
+            scope_depth: 0,
+            locals: vec![],
+            loops: vec![],
+            handlers: vec![],
+        }
+    }
+}
+
 pub struct Compiler {
     parser: Parser,
     scanner: Scanner,
-    current_chunk: Option<StoredChunk>,
+    heap: StoredHeap,         // functions and string literals are allocated on the VM heap
+    globals: StoredNameSpace, // globals of the compiled module, names are resolved to their slots
     debug_mode: bool,
-    scope_depth: usize,
-    locals: Vec<Local>,
+    scopes: Vec<FunctionScope>,
+    classes: Vec<ClassScope>,
 }
 
 #[derive(Copy, Clone, FromRepr, Debug)]
@@ -50,13 +126,20 @@ pub struct Compiler {
 enum Precedence {
     NONE,
     Assignment,
+    Ternary,
     Or,
     And,
+    // bitwise operators bind looser than comparisons, as in C
+    BitOr,
+    BitXor,
+    BitAnd,
     Eq,
     Cmp,
+    Shift,
     Term,
     Factor,
     Unary,
+    Exponent,
     Call,
     Primary,
 }
@@ -71,12 +154,12 @@ struct ParseRule {
 }
 
 use Precedence::*;
-const RULES: [ParseRule; 41] = [
+const RULES: [ParseRule; 71] = [
     /* TOKEN_LEFT_PAREN */
     ParseRule {
         prefix: Some(Compiler::grouping),
-        infix: None,
-        precedence: NONE,
+        infix: Some(Compiler::call),
+        precedence: Call,
     },
     /* TOKEN_RIGHT_PAREN */
     ParseRule {
@@ -86,7 +169,7 @@ const RULES: [ParseRule; 41] = [
     },
     /* TOKEN_LEFT_BRACE */
     ParseRule {
-        prefix: None,
+        prefix: Some(Compiler::map),
         infix: None,
         precedence: NONE,
     },
@@ -96,18 +179,30 @@ const RULES: [ParseRule; 41] = [
         infix: None,
         precedence: NONE,
     },
-    /* TOKEN_COMMA */
+    /* TOKEN_LEFT_BRACKET */
+    ParseRule {
+        prefix: Some(Compiler::list),
+        infix: Some(Compiler::index),
+        precedence: Call,
+    },
+    /* TOKEN_RIGHT_BRACKET */
     ParseRule {
         prefix: None,
         infix: None,
         precedence: NONE,
     },
-    /* TOKEN_DOT */
+    /* TOKEN_COMMA */
     ParseRule {
         prefix: None,
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_DOT */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::dot),
+        precedence: Call,
+    },
     /* TOKEN_MINUS */
     ParseRule {
         prefix: Some(Compiler::unary),
@@ -126,6 +221,18 @@ const RULES: [ParseRule; 41] = [
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_COLON */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_QUESTION */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::ternary),
+        precedence: Ternary,
+    },
     /* TOKEN_SLASH */
     ParseRule {
         prefix: None,
@@ -138,6 +245,24 @@ const RULES: [ParseRule; 41] = [
         infix: Some(Compiler::binary),
         precedence: Factor,
     },
+    /* TOKEN_PERCENT */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: Factor,
+    },
+    /* TOKEN_STAR_STAR */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: Exponent,
+    },
+    /* TOKEN_TILDE_SLASH */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: Factor,
+    },
     /* TOKEN_BANG */
     ParseRule {
         prefix: Some(Compiler::unary),
@@ -192,6 +317,60 @@ const RULES: [ParseRule; 41] = [
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_PLUS_EQUAL */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_MINUS_EQUAL */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_STAR_EQUAL */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_AMPERSAND */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: BitAnd,
+    },
+    /* TOKEN_PIPE */
+    ParseRule {
+        prefix: Some(Compiler::arrow_lambda),
+        infix: Some(Compiler::binary),
+        precedence: BitOr,
+    },
+    /* TOKEN_CARET */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: BitXor,
+    },
+    /* TOKEN_TILDE */
+    ParseRule {
+        prefix: Some(Compiler::unary),
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_LESS_LESS */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: Shift,
+    },
+    /* TOKEN_GREATER_GREATER */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: Shift,
+    },
     /* TOKEN_IDENTIFIER */
     ParseRule {
         prefix: Some(Compiler::variable),
@@ -204,43 +383,103 @@ const RULES: [ParseRule; 41] = [
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_INTERPOLATION */
+    ParseRule {
+        prefix: Some(Compiler::interpolation),
+        infix: None,
+        precedence: NONE,
+    },
     /* TOKEN_NUMBER */
     ParseRule {
         prefix: Some(Compiler::number),
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_INTEGER */
+    ParseRule {
+        prefix: Some(Compiler::integer),
+        infix: None,
+        precedence: NONE,
+    },
     /* TOKEN_AND */
     ParseRule {
         prefix: None,
         infix: Some(Compiler::and),
         precedence: And,
     },
+    /* TOKEN_AS */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_BREAK */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_CATCH */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
     /* TOKEN_CLASS */
     ParseRule {
         prefix: None,
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_CONST */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_CONTINUE */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
     /* TOKEN_ELSE */
     ParseRule {
         prefix: None,
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_EXPORT */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
     /* TOKEN_FALSE */
     ParseRule {
         prefix: Some(Compiler::literal),
         infix: None,
         precedence: NONE,
     },
-    /* TOKEN_FOR */
+    /* TOKEN_FINALLY */
     ParseRule {
         prefix: None,
         infix: None,
         precedence: NONE,
     },
     /* TOKEN_FUN */
+    ParseRule {
+        prefix: Some(Compiler::lambda),
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_FOR */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_FROM */
     ParseRule {
         prefix: None,
         infix: None,
@@ -252,6 +491,18 @@ const RULES: [ParseRule; 41] = [
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_IMPORT */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_IN */
+    ParseRule {
+        prefix: None,
+        infix: Some(Compiler::binary),
+        precedence: Cmp,
+    },
     /* TOKEN_NIL */
     ParseRule {
         prefix: Some(Compiler::literal),
@@ -278,11 +529,17 @@ const RULES: [ParseRule; 41] = [
     },
     /* TOKEN_SUPER */
     ParseRule {
-        prefix: None,
+        prefix: Some(Compiler::super_),
         infix: None,
         precedence: NONE,
     },
     /* TOKEN_THIS */
+    ParseRule {
+        prefix: Some(Compiler::this),
+        infix: None,
+        precedence: NONE,
+    },
+    /* TOKEN_THROW */
     ParseRule {
         prefix: None,
         infix: None,
@@ -294,6 +551,12 @@ const RULES: [ParseRule; 41] = [
         infix: None,
         precedence: NONE,
     },
+    /* TOKEN_TRY */
+    ParseRule {
+        prefix: None,
+        infix: None,
+        precedence: NONE,
+    },
     /* TOKEN_VAR */
     ParseRule {
         prefix: None,
@@ -321,21 +584,30 @@ const RULES: [ParseRule; 41] = [
 ];
 
 impl Compiler {
-    pub fn from_source(source: String, debug_mode: bool) -> Self {
+    pub fn from_source(
+        source: String,
+        globals: StoredNameSpace,
+        heap: StoredHeap,
+        debug_mode: bool,
+    ) -> Self {
         let scanner = Scanner::new(source);
         let parser = Parser::new();
         Self {
             parser,
             scanner,
-            current_chunk: None,
+            heap,
+            globals,
             debug_mode,
-            scope_depth: 0,
-            locals: vec![],
+            scopes: vec![],
+            classes: vec![],
         }
     }
 
     pub fn compile(&mut self, chunk: StoredChunk) -> VoidResult {
-        self.current_chunk = Some(chunk.clone());
+        self.scopes.push(FunctionScope::new(
+            FunctionObject::with_chunk(None, chunk),
+            FunctionKind::Script,
+        ));
 
         self.advance()?;
         while !self.matches(&TokenType::EOF)? {
@@ -344,6 +616,18 @@ impl Compiler {
         Ok(())
     }
 
+    fn scope(&self) -> &FunctionScope {
+        self.scopes.last().expect("No function scope")
+    }
+
+    fn scope_mut(&mut self) -> &mut FunctionScope {
+        self.scopes.last_mut().expect("No function scope")
+    }
+
+    fn current_chunk(&self) -> &StoredChunk {
+        &self.scope().function.chunk
+    }
+
     fn previous(&self) -> Option<&Rc<Token>> {
         self.parser.previous.as_ref()
     }
@@ -395,7 +679,7 @@ impl Compiler {
         print!("[line {}] Error", token.line);
         match token.token_type {
             TokenType::EOF => print!(" at end"),
-            TokenType::Error => {}
+            TokenType::Error => print!(" at column {}", token.column),
             _ => print!(
                 " at '{}'",
                 self.scanner.substr(token.start, token.start + token.length)
@@ -417,25 +701,24 @@ impl Compiler {
         if self.debug_mode {
             println!("Emitted opcode: {kind}")
         }
-        self.current_chunk
-            .as_ref()
-            .unwrap()
+        self.current_chunk()
             .borrow_mut()
-            .push(OpCode::new(kind, self.line()));
+            .push(kind, self.line());
     }
 
-    fn emit_const(&self, value: StoredValue) {
+    fn emit_const(&self, value: Value) -> VoidResult {
         self.emit_op_code(OpCodeKind::Const {
-            const_idx: self.make_const(value),
+            const_idx: self.make_const(value)?,
         });
+        Ok(())
     }
 
-    fn make_const(&self, value: StoredValue) -> usize {
-        self.current_chunk
-            .as_ref()
-            .unwrap()
-            .borrow_mut()
-            .push_const(value)
+    fn make_const(&self, value: Value) -> Result<usize, Error> {
+        // constant index must fit into the widest operand
+        if self.current_chunk().borrow().constants.len() > MAX_INDEX {
+            return Err(self.error("Too many constants in one chunk".to_owned()));
+        }
+        Ok(self.current_chunk().borrow_mut().push_const(value))
     }
 
     fn line(&self) -> usize {
@@ -474,6 +757,23 @@ impl Compiler {
             self.print_statement()
         } else if self.matches(&TokenType::VAR)? {
             self.var_statement()
+        } else if self.matches(&TokenType::CONST)? {
+            self.const_statement()
+        } else if self.matches(&TokenType::FUN)? {
+            if !self.check(&TokenType::IDENTIFIER) {
+                // anonymous function at the start of expression statement
+                self.lambda(true)?;
+                return self.finish_expr_statement();
+            }
+            self.fun_statement()
+        } else if self.matches(&TokenType::CLASS)? {
+            self.class_statement()
+        } else if self.matches(&TokenType::RETURN)? {
+            self.return_statement()
+        } else if self.matches(&TokenType::BREAK)? {
+            self.break_statement()
+        } else if self.matches(&TokenType::CONTINUE)? {
+            self.continue_statement()
         } else if self.matches(&TokenType::LeftBrace)? {
             self.begin_scope();
             self.block()?;
@@ -485,6 +785,16 @@ impl Compiler {
             self.while_statement()
         } else if self.matches(&TokenType::FOR)? {
             self.for_statement()
+        } else if self.matches(&TokenType::IMPORT)? {
+            self.import_statement()
+        } else if self.matches(&TokenType::FROM)? {
+            self.selective_import_statement()
+        } else if self.matches(&TokenType::EXPORT)? {
+            self.export_statement()
+        } else if self.matches(&TokenType::THROW)? {
+            self.throw_statement()
+        } else if self.matches(&TokenType::TRY)? {
+            self.try_statement()
         } else {
             self.expr_statement()
         }
@@ -495,32 +805,36 @@ impl Compiler {
         if local_count == 0 {
             return None;
         }
-        self.locals.get_mut(local_count - 1)
+        self.scope_mut().locals.get_mut(local_count - 1)
     }
 
     fn begin_scope(&mut self) {
-        self.scope_depth += 1;
+        self.scope_mut().scope_depth += 1;
     }
 
     fn local_count(&self) -> usize {
-        self.locals.len()
+        self.scope().locals.len()
     }
 
     fn end_scope(&mut self) {
-        self.scope_depth -= 1;
-        while self.local_count() > 0 && self.last_local().unwrap().depth > self.scope_depth {
-            // removing locals of exited scope
-            self.emit_op_code(OpCodeKind::Pop);
-            self.locals.pop();
+        self.scope_mut().scope_depth -= 1;
+        while self.local_count() > 0 && self.last_local().unwrap().depth > self.scope().scope_depth {
+            // removing locals of exited scope, captured ones are moved to the heap
+            if self.last_local().unwrap().is_captured {
+                self.emit_op_code(OpCodeKind::CloseUpvalue);
+            } else {
+                self.emit_op_code(OpCodeKind::Pop);
+            }
+            self.scope_mut().locals.pop();
         }
     }
 
     fn is_local_scope(&self) -> bool {
-        self.scope_depth > 0
+        self.scope().scope_depth > 0
     }
 
     fn is_global_scope(&self) -> bool {
-        self.scope_depth == 0
+        self.scope().scope_depth == 0
     }
 
     fn block(&mut self) -> VoidResult {
@@ -541,6 +855,14 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compiles rest of expression statement whose prefix is already compiled
+    fn finish_expr_statement(&mut self) -> VoidResult {
+        self.parse_infix(Assignment, true)?;
+        self.consume(TokenType::SEMICOLON, "Expected ';'".to_owned())?;
+        self.emit_op_code(OpCodeKind::Pop);
+        Ok(())
+    }
+
     fn print_statement(&mut self) -> VoidResult {
         self.expression()?;
         self.consume(TokenType::SEMICOLON, "Expected ';'".to_owned())?;
@@ -565,61 +887,344 @@ impl Compiler {
         Ok(())
     }
 
-    fn identifier_constant(&mut self, literal: Literal) -> usize {
-        self.make_const(rc_refcell!(Value::Identifier(literal,)))
-    }
-
-    fn parse_variable_name(&mut self, message: String) -> Result<usize, Error> {
-        self.consume(TokenType::IDENTIFIER, message)?;
-        self.declare_variable()?;
+    fn const_statement(&mut self) -> VoidResult {
+        let global = self.parse_variable_name("Expected constant name".to_owned())?;
+        self.consume(
+            TokenType::EQUAL,
+            "Expected '=' after constant name, constants must be initialized".to_owned(),
+        )?;
+        self.expression()?;
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after constant declaration".to_owned(),
+        )?;
 
         if self.is_local_scope() {
-            return Ok(0);
-            // At runtime, locals aren’t looked up by name.
-            // There’s no need to stuff the variable’s name into the constant table,
-            // so if the declaration is inside a local scope, we return a dummy table index instead.
+            self.last_local().unwrap().is_const = true;
+            self.mark_initialized();
+        } else {
+            self.emit_op_code(OpCodeKind::DefineConst { slot: global });
         }
+        Ok(())
+    }
 
-        Ok(self.identifier_constant(self.previous_string_literal()?))
+    fn fun_statement(&mut self) -> VoidResult {
+        let global = self.parse_variable_name("Expected function name".to_owned())?;
+        // function may refer to itself inside its body
+        self.mark_initialized();
+        self.function(FunctionKind::Function)?;
+        self.define_global(global);
+        Ok(())
     }
 
-    fn declare_variable(&mut self) -> VoidResult {
-        if self.is_global_scope() {
-            return Ok(());
-        }
+    fn function(&mut self, kind: FunctionKind) -> VoidResult {
+        let name = self.previous().unwrap().literal.clone();
+        self.begin_function(name, kind);
 
-        let local_name = self.previous().unwrap();
-        if local_name.literal.is_none() {
-            return Err(self.error("Expected literal".to_owned()));
+        self.consume(
+            TokenType::LeftParen,
+            "Expected '(' after function name".to_owned(),
+        )?;
+        self.parameters(&TokenType::RightParen)?;
+        self.consume(
+            TokenType::RightParen,
+            "Expected ')' after parameters".to_owned(),
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expected '{' before function body".to_owned(),
+        )?;
+        self.block()?;
+        self.end_closure()
+    }
+
+    /// fn (a, b) { ... } used as expression
+    #[allow(unused_variables)]
+    fn lambda(&mut self, can_assign: bool) -> VoidResult {
+        self.begin_function(Some(Rc::new("lambda".to_owned())), FunctionKind::Function);
+
+        self.consume(TokenType::LeftParen, "Expected '(' after 'fn'".to_owned())?;
+        self.parameters(&TokenType::RightParen)?;
+        self.consume(
+            TokenType::RightParen,
+            "Expected ')' after parameters".to_owned(),
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expected '{' before function body".to_owned(),
+        )?;
+        self.block()?;
+        self.end_closure()
+    }
+
+    /// |a, b| a + b, value of the body expression is returned
+    #[allow(unused_variables)]
+    fn arrow_lambda(&mut self, can_assign: bool) -> VoidResult {
+        self.begin_function(Some(Rc::new("lambda".to_owned())), FunctionKind::Function);
+
+        self.parameters(&TokenType::PIPE)?;
+        self.consume(TokenType::PIPE, "Expected '|' after parameters".to_owned())?;
+        self.expression()?;
+        self.emit_op_code(OpCodeKind::Return);
+        self.end_closure()
+    }
+
+    fn begin_function(&mut self, name: Option<Literal>, kind: FunctionKind) {
+        self.scopes
+            .push(FunctionScope::new(FunctionObject::new(name), kind));
+        // slot zero is reserved for the called function itself or ``this`` in methods
+        let reserved_name = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => Some(Rc::new("this".to_owned())),
+            FunctionKind::Script | FunctionKind::Function => None,
+        };
+        let reserved = Token::new(
+            TokenType::IDENTIFIER,
+            self.line(),
+            0,
+            0,
+            0,
+            reserved_name,
+            None,
+        );
+        self.scope_mut()
+            .locals
+            .push(Local::new(Rc::new(reserved), 0, true));
+        self.begin_scope();
+    }
+
+    fn parameters(&mut self, closing: &TokenType) -> VoidResult {
+        if !self.check(closing) {
+            loop {
+                self.scope_mut().function.arity += 1;
+                if self.scope().function.arity > MAX_ARGS {
+                    return Err(self.error_at_current(format!(
+                        "Cannot have more than {MAX_ARGS} parameters"
+                    )));
+                }
+                let param = self.parse_variable_name("Expected parameter name".to_owned())?;
+                self.define_global(param);
+                if !self.matches(&TokenType::COMMA)? {
+                    break;
+                }
+            }
         }
-        self.add_local(local_name.clone());
         Ok(())
     }
 
-    fn add_local(&mut self, name: Rc<Token>) {
-        let local = Local::new(name, self.scope_depth, false);
-        self.locals.push(local);
+    /// Finishes function and emits closure creation in the enclosing one
+    fn end_closure(&mut self) -> VoidResult {
+        let function = self.end_function();
+        let function = self.heap.borrow_mut().alloc(function);
+        let const_idx = self.make_const(Value::Object(function))?;
+        self.emit_op_code(OpCodeKind::Closure { const_idx });
+        Ok(())
     }
 
-    fn define_global(&mut self, name_idx: usize) {
-        if self.is_local_scope() {
-            self.last_local().unwrap().mark_initialized();
-            return;
+    fn end_function(&mut self) -> FunctionObject {
+        self.emit_return();
+        let scope = self.scopes.pop().expect("No function scope");
+        if self.debug_mode {
+            println!("Compiled {}:", scope.function);
+            println!("{}", scope.function.chunk.borrow());
         }
-        self.emit_op_code(OpCodeKind::DefineGlobal { name_idx });
+        scope.function
     }
 
-    fn variable(&mut self, can_assign: bool) -> VoidResult {
-        self.named_variable(self.previous_string_literal()?, can_assign)
+    fn emit_return(&self) {
+        self.emit_return_value();
+        self.emit_op_code(OpCodeKind::Return);
     }
 
-    #[allow(clippy::unnecessary_unwrap)]
+    fn emit_return_value(&self) {
+        if self.scope().kind == FunctionKind::Initializer {
+            // initializer always returns the instance
+            self.emit_op_code(OpCodeKind::ReadLocal { name_idx: 0 });
+        } else {
+            self.emit_op_code(OpCodeKind::Null);
+        }
+    }
+
+    fn return_statement(&mut self) -> VoidResult {
+        if self.scope().kind == FunctionKind::Script {
+            return Err(self.error("Cannot return from top-level code".to_owned()));
+        }
+        if self.matches(&TokenType::SEMICOLON)? {
+            self.emit_return_value();
+            return self.emit_exit(Exit::Return);
+        }
+
+        if self.scope().kind == FunctionKind::Initializer {
+            return Err(self.error("Cannot return a value from an initializer".to_owned()));
+        }
+
+        self.expression()?;
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after return value".to_owned(),
+        )?;
+        self.emit_exit(Exit::Return)
+    }
+
+    fn class_statement(&mut self) -> VoidResult {
+        self.consume(TokenType::IDENTIFIER, "Expected class name".to_owned())?;
+        let class_name = self.previous_string_literal()?;
+        let name_idx = self.identifier_constant(class_name.clone())?;
+        self.declare_variable()?;
+        let global = self.declared_variable_slot()?;
+
+        self.emit_op_code(OpCodeKind::Class { name_idx });
+        self.define_global(global);
+        self.classes.push(ClassScope {
+            has_superclass: false,
+        });
+
+        if self.matches(&TokenType::LESS)? {
+            self.consume(TokenType::IDENTIFIER, "Expected superclass name".to_owned())?;
+            let superclass_name = self.previous_string_literal()?;
+            if superclass_name == class_name {
+                return Err(self.error("A class cannot inherit from itself".to_owned()));
+            }
+            self.named_variable(superclass_name, false)?;
+
+            // superclass is kept in a local named ``super``, so methods capture it as upvalue
+            self.begin_scope();
+            let super_token = Token::new(
+                TokenType::IDENTIFIER,
+                self.line(),
+                0,
+                0,
+                0,
+                Some(Rc::new("super".to_owned())),
+                None,
+            );
+            self.add_local(Rc::new(super_token));
+            self.mark_initialized();
+
+            self.named_variable(class_name.clone(), false)?;
+            self.emit_op_code(OpCodeKind::Inherit);
+            self.classes.last_mut().unwrap().has_superclass = true;
+        }
+
+        // class stays on the stack while its methods are being bound
+        self.named_variable(class_name, false)?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expected '{' before class body".to_owned(),
+        )?;
+        while !self.check(&TokenType::RightBrace) && !self.check(&TokenType::EOF) {
+            self.method()?;
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expected '}' after class body".to_owned(),
+        )?;
+        self.emit_op_code(OpCodeKind::Pop);
+
+        if self.classes.pop().unwrap().has_superclass {
+            self.end_scope();
+        }
+        Ok(())
+    }
+
+    fn method(&mut self) -> VoidResult {
+        self.consume(TokenType::IDENTIFIER, "Expected method name".to_owned())?;
+        let method_name = self.previous_string_literal()?;
+        let name_idx = self.identifier_constant(method_name.clone())?;
+
+        let kind = if method_name.as_str() == "init" {
+            FunctionKind::Initializer
+        } else {
+            FunctionKind::Method
+        };
+        self.function(kind)?;
+        self.emit_op_code(OpCodeKind::Method { name_idx });
+        Ok(())
+    }
+
+    /// Slot of the global in the module namespace, reserved on the first mention
+    fn global_slot(&mut self, literal: Literal) -> usize {
+        let symbol = self.heap.borrow_mut().intern(&literal);
+        self.globals.borrow_mut().resolve(symbol)
+    }
+
+    fn identifier_constant(&mut self, literal: Literal) -> Result<usize, Error> {
+        let symbol = self.heap.borrow_mut().intern(&literal);
+        self.make_const(Value::Identifier(symbol))
+    }
+
+    fn parse_variable_name(&mut self, message: String) -> Result<usize, Error> {
+        self.consume(TokenType::IDENTIFIER, message)?;
+        self.declare_variable()?;
+        self.declared_variable_slot()
+    }
+
+    fn declared_variable_slot(&mut self) -> Result<usize, Error> {
+        if self.is_local_scope() {
+            return Ok(0);
+            // At runtime, locals aren’t looked up by name.
+            // There’s no need to reserve a global slot for the variable,
+            // so if the declaration is inside a local scope, we return a dummy slot instead.
+        }
+
+        Ok(self.global_slot(self.previous_string_literal()?))
+    }
+
+    fn declare_variable(&mut self) -> VoidResult {
+        if self.is_global_scope() {
+            return Ok(());
+        }
+
+        let local_name = self.previous().unwrap();
+        if local_name.literal.is_none() {
+            return Err(self.error("Expected literal".to_owned()));
+        }
+        self.add_local(local_name.clone());
+        Ok(())
+    }
+
+    fn add_local(&mut self, name: Rc<Token>) {
+        let local = Local::new(name, self.scope().scope_depth, false);
+        self.scope_mut().locals.push(local);
+    }
+
+    /// Stack slot used by the compiled code itself, it cannot be referenced by name
+    fn add_hidden_local(&mut self) {
+        let name = Token::new(TokenType::IDENTIFIER, self.line(), 0, 0, 0, None, None);
+        let local = Local::new(Rc::new(name), self.scope().scope_depth, true);
+        self.scope_mut().locals.push(local);
+    }
+
+    fn mark_initialized(&mut self) {
+        if self.is_global_scope() {
+            return;
+        }
+        self.last_local().unwrap().mark_initialized();
+    }
+
+    fn define_global(&mut self, slot: usize) {
+        if self.is_local_scope() {
+            self.mark_initialized();
+            return;
+        }
+        self.emit_op_code(OpCodeKind::DefineGlobal { slot });
+    }
+
+    fn variable(&mut self, can_assign: bool) -> VoidResult {
+        self.named_variable(self.previous_string_literal()?, can_assign)
+    }
+
+    #[allow(clippy::unnecessary_unwrap)]
     #[allow(clippy::needless_late_init)]
     fn named_variable(&mut self, name: Literal, can_assign: bool) -> VoidResult {
         let get_op: OpCodeKind;
         let set_op: OpCodeKind;
 
         let local_idx = self.resolve_local(&name)?;
+        let upvalue_idx = if local_idx.is_none() {
+            self.resolve_upvalue(self.scopes.len() - 1, &name)?
+        } else {
+            None
+        };
         if local_idx.is_some() {
             get_op = OpCodeKind::ReadLocal {
                 name_idx: local_idx.unwrap(),
@@ -627,28 +1232,66 @@ impl Compiler {
             set_op = OpCodeKind::SetLocal {
                 name_idx: local_idx.unwrap(),
             }
+        } else if let Some(upvalue_idx) = upvalue_idx {
+            get_op = OpCodeKind::ReadUpvalue { upvalue_idx };
+            set_op = OpCodeKind::SetUpvalue { upvalue_idx };
         } else {
-            let name_idx = self.identifier_constant(name);
-            get_op = OpCodeKind::ReadGlobal { name_idx };
-            set_op = OpCodeKind::SetGlobal { name_idx }
+            let slot = self.global_slot(name.clone());
+            get_op = OpCodeKind::ReadGlobal { slot };
+            set_op = OpCodeKind::SetGlobal { slot }
         }
+        // constant globals are checked by the VM
+        let is_const = (local_idx.is_some() || upvalue_idx.is_some()) && self.is_const_local(&name);
 
         if can_assign && self.matches(&TokenType::EQUAL)? {
+            if is_const {
+                return Err(self.error(format!("Cannot assign to constant '{name}'")));
+            }
             self.expression()?;
             self.emit_op_code(set_op);
+        } else if let Some(op) = self.compound_assignment(can_assign)? {
+            if is_const {
+                return Err(self.error(format!("Cannot assign to constant '{name}'")));
+            }
+            // a += b is compiled as a = a + b
+            self.emit_op_code(get_op);
+            self.expression()?;
+            self.emit_op_code(op);
+            self.emit_op_code(set_op);
         } else {
             self.emit_op_code(get_op);
         }
         Ok(())
     }
 
+    /// Consumes compound assignment operator, returning opcode of its arithmetic part
+    fn compound_assignment(&mut self, can_assign: bool) -> Result<Option<OpCodeKind>, Error> {
+        if !can_assign {
+            return Ok(None);
+        }
+        let op = match self.current().unwrap().token_type {
+            TokenType::PlusEqual => OpCodeKind::Add,
+            TokenType::MinusEqual => OpCodeKind::Sub,
+            TokenType::StarEqual => OpCodeKind::Mul,
+            TokenType::SlashEqual => OpCodeKind::Div,
+            _ => return Ok(None),
+        };
+        self.advance()?;
+        Ok(Some(op))
+    }
+
     fn resolve_local(&self, name: &Literal) -> Result<Option<usize>, Error> {
-        if self.is_global_scope() {
+        self.resolve_local_at(self.scopes.len() - 1, name)
+    }
+
+    fn resolve_local_at(&self, scope_idx: usize, name: &Literal) -> Result<Option<usize>, Error> {
+        let scope = &self.scopes[scope_idx];
+        if scope.scope_depth == 0 {
             return Ok(None);
         }
 
-        for i in (0..self.local_count()).rev() {
-            let local = &self.locals[i];
+        for i in (0..scope.locals.len()).rev() {
+            let local = &scope.locals[i];
             if local.name.literal.as_ref().is_some_and(|x| x == name) {
                 if !local.is_initialized {
                     return Err(self
@@ -660,38 +1303,78 @@ impl Compiler {
         Ok(None)
     }
 
+    /// Whether innermost local visible by this name, possibly in enclosing function, is constant
+    fn is_const_local(&self, name: &Literal) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.locals.iter().rev())
+            .find(|local| local.name.literal.as_ref() == Some(name))
+            .is_some_and(|local| local.is_const)
+    }
+
+    /// Looks up variable in enclosing functions, capturing it into every function in between
+    fn resolve_upvalue(&mut self, scope_idx: usize, name: &Literal) -> Result<Option<usize>, Error> {
+        if scope_idx == 0 {
+            return Ok(None);
+        }
+        let enclosing_idx = scope_idx - 1;
+
+        if let Some(local_idx) = self.resolve_local_at(enclosing_idx, name)? {
+            self.scopes[enclosing_idx].locals[local_idx].is_captured = true;
+            return Ok(Some(self.add_upvalue(scope_idx, local_idx, true)?));
+        }
+
+        if let Some(upvalue_idx) = self.resolve_upvalue(enclosing_idx, name)? {
+            return Ok(Some(self.add_upvalue(scope_idx, upvalue_idx, false)?));
+        }
+        Ok(None)
+    }
+
+    fn add_upvalue(&mut self, scope_idx: usize, index: usize, is_local: bool) -> Result<usize, Error> {
+        let descriptor = UpvalueDescriptor { index, is_local };
+        let upvalues = &self.scopes[scope_idx].function.upvalues;
+
+        if let Some(existing) = upvalues.iter().position(|x| *x == descriptor) {
+            return Ok(existing);
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            return Err(self.error(format!(
+                "Cannot capture more than {MAX_UPVALUES} variables in function"
+            )));
+        }
+
+        self.scopes[scope_idx].function.upvalues.push(descriptor);
+        Ok(self.scopes[scope_idx].function.upvalues.len() - 1)
+    }
+
     fn emit_jump(&mut self, kind: OpCodeKind) -> usize {
+        let jump_idx = self.current_chunk().borrow().len();
         self.emit_op_code(kind);
-        self.current_chunk.as_ref().unwrap().borrow().len() - 1
+        jump_idx
     }
 
-    fn patch_jump(&mut self, jump_idx: usize) {
-        let jump = self.current_chunk.as_ref().unwrap().borrow().len() - 1 - jump_idx;
-        let mut mut_chunk = self.current_chunk.as_ref().unwrap().borrow_mut();
-        let op_code = mut_chunk
-            .get(jump_idx)
+    fn patch_jump(&mut self, jump_idx: usize) -> VoidResult {
+        let mut mut_chunk = self.current_chunk().borrow_mut();
+        let (_, next) = mut_chunk
+            .decode(jump_idx)
             .expect("Invalid jump offset in patch_jump()");
-
-        match &mut op_code.kind() {
-            OpCodeKind::JumpIfFalse { .. } => {
-                mut_chunk.replace(
-                    jump_idx,
-                    OpCode::new(OpCodeKind::JumpIfFalse { offset: jump }, self.line()),
-                );
-            }
-            OpCodeKind::Jump { .. } => {
-                mut_chunk.replace(
-                    jump_idx,
-                    OpCode::new(OpCodeKind::Jump { offset: jump }, self.line()),
-                );
-            }
-            _ => unreachable!(),
+        let jump = mut_chunk.len() - next;
+        if jump > MAX_JUMP {
+            return Err(self.error("Too much code to jump over".to_owned()));
         }
+        mut_chunk.patch_jump(jump_idx, jump);
+        Ok(())
     }
 
-    fn emit_loop(&mut self, loop_start: usize) {
-        let offset = self.current_chunk.as_ref().unwrap().borrow().len() - loop_start;
+    fn emit_loop(&mut self, loop_start: usize) -> VoidResult {
+        // jumps over the loop instruction itself too
+        let offset = self.current_chunk().borrow().len() + JUMP_SIZE - loop_start;
+        if offset > MAX_JUMP {
+            return Err(self.error("Loop body is too large".to_owned()));
+        }
         self.emit_op_code(OpCodeKind::Loop { offset });
+        Ok(())
     }
 
     fn if_statement(&mut self) -> VoidResult {
@@ -708,19 +1391,19 @@ impl Compiler {
         self.statement()?;
         let else_jump = self.emit_jump(OpCodeKind::Jump { offset: 0 });
 
-        self.patch_jump(then_jump);
+        self.patch_jump(then_jump)?;
 
         self.emit_op_code(OpCodeKind::Pop);
         if self.matches(&TokenType::ELSE)? {
             self.statement()?;
         }
 
-        self.patch_jump(else_jump);
+        self.patch_jump(else_jump)?;
         Ok(())
     }
 
     fn while_statement(&mut self) -> VoidResult {
-        let loop_start = self.current_chunk.as_ref().unwrap().borrow().len();
+        let loop_start = self.current_chunk().borrow().len();
         self.consume(TokenType::LeftParen, "Expected '(' after while".to_owned())?;
         self.expression()?;
         self.consume(
@@ -730,15 +1413,333 @@ impl Compiler {
 
         let exit_jump = self.emit_jump(OpCodeKind::JumpIfFalse { offset: 0 });
         self.emit_op_code(OpCodeKind::Pop);
+        self.begin_loop(loop_start);
         self.statement()?;
 
-        self.emit_loop(loop_start);
+        self.emit_loop(loop_start)?;
+
+        self.patch_jump(exit_jump)?;
+        self.emit_op_code(OpCodeKind::Pop);
+        self.end_loop()?;
+        Ok(())
+    }
+
+    fn begin_loop(&mut self, continue_target: usize) {
+        let scope_depth = self.scope().scope_depth;
+        let handlers_count = self.scope().handlers.len();
+        self.scope_mut().loops.push(LoopScope {
+            continue_target,
+            scope_depth,
+            handlers_count,
+            break_jumps: vec![],
+        });
+    }
+
+    fn end_loop(&mut self) -> VoidResult {
+        let loop_scope = self.scope_mut().loops.pop().expect("No loop scope");
+        for break_jump in loop_scope.break_jumps {
+            self.patch_jump(break_jump)?;
+        }
+        Ok(())
+    }
+
+    /// Emits pops for locals declared inside the loop body, keeping them in the compiler
+    fn discard_loop_locals(&mut self, loop_depth: usize) {
+        let ops: Vec<OpCodeKind> = self
+            .scope()
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > loop_depth)
+            .map(|local| {
+                if local.is_captured {
+                    OpCodeKind::CloseUpvalue
+                } else {
+                    OpCodeKind::Pop
+                }
+            })
+            .collect();
+        for op in ops {
+            self.emit_op_code(op);
+        }
+    }
+
+    fn break_statement(&mut self) -> VoidResult {
+        if self.scope().loops.is_empty() {
+            return Err(self.error("Cannot use 'break' outside of a loop".to_owned()));
+        }
+        self.consume(TokenType::SEMICOLON, "Expected ';' after 'break'".to_owned())?;
+        self.emit_exit(Exit::Break)
+    }
+
+    fn continue_statement(&mut self) -> VoidResult {
+        if self.scope().loops.is_empty() {
+            return Err(self.error("Cannot use 'continue' outside of a loop".to_owned()));
+        }
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after 'continue'".to_owned(),
+        )?;
+        self.emit_exit(Exit::Continue)
+    }
+
+    /// Jumps to the target of ``exit``, going through the innermost pending finally first.
+    /// Return value is expected on top of the stack
+    fn emit_exit(&mut self, exit: Exit) -> VoidResult {
+        let target_handlers = match exit {
+            Exit::Return => 0,
+            Exit::Break | Exit::Continue => self.scope().loops.last().unwrap().handlers_count,
+        };
+        if self.scope().handlers.len() > target_handlers {
+            // finally expects the pending value and flag in place of the protected block locals
+            if exit != Exit::Return {
+                self.emit_op_code(OpCodeKind::Null);
+            }
+            let depth = self.scope().handlers.last().unwrap().depth;
+            self.emit_op_code(OpCodeKind::Unwind { depth });
+            self.emit_op_code(OpCodeKind::PopHandler);
+            self.emit_const(Value::Int(exit.flag()))?;
+            let jump = self.emit_jump(OpCodeKind::Jump { offset: 0 });
+            self.scope_mut()
+                .handlers
+                .last_mut()
+                .unwrap()
+                .exits
+                .push((exit, jump));
+            return Ok(());
+        }
+
+        match exit {
+            Exit::Return => self.emit_op_code(OpCodeKind::Return),
+            Exit::Break => {
+                let loop_depth = self.scope().loops.last().unwrap().scope_depth;
+                self.discard_loop_locals(loop_depth);
+                let jump = self.emit_jump(OpCodeKind::Jump { offset: 0 });
+                self.scope_mut()
+                    .loops
+                    .last_mut()
+                    .unwrap()
+                    .break_jumps
+                    .push(jump);
+            }
+            Exit::Continue => {
+                let loop_scope = self.scope().loops.last().unwrap();
+                let (loop_depth, continue_target) =
+                    (loop_scope.scope_depth, loop_scope.continue_target);
+                self.discard_loop_locals(loop_depth);
+                self.emit_loop(continue_target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn module_path(&mut self) -> Result<usize, Error> {
+        self.consume(TokenType::STRING, "Expected module path string".to_owned())?;
+        let path = self.previous().unwrap().literal.clone().unwrap();
+        self.identifier_constant(path)
+    }
+
+    /// import "path/to/lib.lox" as lib;
+    fn import_statement(&mut self) -> VoidResult {
+        let path_idx = self.module_path()?;
+        self.consume(TokenType::AS, "Expected 'as' after module path".to_owned())?;
+        let global = self.parse_variable_name("Expected module name after 'as'".to_owned())?;
+        self.emit_op_code(OpCodeKind::Import { path_idx });
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after import".to_owned(),
+        )?;
+        self.define_global(global);
+        Ok(())
+    }
+
+    /// from "path/to/lib.lox" import a, b; defines each exported name as a variable
+    fn selective_import_statement(&mut self) -> VoidResult {
+        let path_idx = self.module_path()?;
+        self.consume(
+            TokenType::IMPORT,
+            "Expected 'import' after module path".to_owned(),
+        )?;
+        loop {
+            let global = self.parse_variable_name("Expected imported name".to_owned())?;
+            let name_idx = self.identifier_constant(self.previous_string_literal()?)?;
+            // module is cached after first import, so loading it per name is cheap
+            self.emit_op_code(OpCodeKind::Import { path_idx });
+            self.emit_op_code(OpCodeKind::GetProperty { name_idx });
+            self.define_global(global);
+            if !self.matches(&TokenType::COMMA)? {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after import".to_owned(),
+        )
+    }
+
+    fn export_statement(&mut self) -> VoidResult {
+        if self.scope().kind != FunctionKind::Script || self.is_local_scope() {
+            return Err(self.error("Can only export top-level declarations".to_owned()));
+        }
+        let is_declaration = [
+            TokenType::VAR,
+            TokenType::CONST,
+            TokenType::FUN,
+            TokenType::CLASS,
+        ]
+            .iter()
+            .any(|token_type| self.check(token_type));
+        if !is_declaration {
+            return Err(self.error_at_current(
+                "Expected variable, function or class declaration after 'export'".to_owned(),
+            ));
+        }
+
+        // name follows declaration keyword
+        self.advance()?;
+        let Some(name) = self.current().and_then(|token| token.literal.clone()) else {
+            return Err(self.error_at_current("Expected declaration name".to_owned()));
+        };
+        match self.previous().unwrap().token_type {
+            TokenType::VAR => self.var_statement()?,
+            TokenType::CONST => self.const_statement()?,
+            TokenType::FUN => self.fun_statement()?,
+            _ => self.class_statement()?,
+        }
+        let name_idx = self.identifier_constant(name)?;
+        self.emit_op_code(OpCodeKind::Export { name_idx });
+        Ok(())
+    }
+
+    fn throw_statement(&mut self) -> VoidResult {
+        self.expression()?;
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expected ';' after thrown value".to_owned(),
+        )?;
+        self.emit_op_code(OpCodeKind::Throw);
+        Ok(())
+    }
+
+    /// Installs handler that unwinds the stack down to ``depth`` locals and jumps to the patched offset
+    fn begin_handler(&mut self, depth: usize) -> usize {
+        self.scope_mut().handlers.push(HandlerScope {
+            depth,
+            exits: vec![],
+        });
+        self.emit_jump(OpCodeKind::PushHandler { offset: 0, depth })
+    }
+
+    /// Removes handler on normal exit, returns jumps of the exits that left the block early
+    fn end_handler(&mut self) -> Vec<(Exit, usize)> {
+        self.emit_op_code(OpCodeKind::PopHandler);
+        let handler = self.scope_mut().handlers.pop().expect("No handler scope");
+        handler.exits
+    }
 
-        self.patch_jump(exit_jump);
+    /// Finally block starts with the pending error and a flag whether it should be rethrown on the stack,
+    /// early exits put their return value and flag there instead and are repeated after finally
+    /// Repeats ``exit`` after finally when the flag at ``slot`` + 1 says it is pending
+    fn emit_pending_exit(&mut self, exit: Exit, slot: usize) -> VoidResult {
+        self.emit_op_code(OpCodeKind::ReadLocal { name_idx: slot + 1 });
+        self.emit_const(Value::Int(exit.flag()))?;
+        self.emit_op_code(OpCodeKind::Eq);
+        let skip = self.emit_jump(OpCodeKind::JumpIfFalse { offset: 0 });
+        self.emit_op_code(OpCodeKind::Pop);
+        if exit == Exit::Return {
+            self.emit_op_code(OpCodeKind::ReadLocal { name_idx: slot });
+        }
+        self.emit_exit(exit)?;
+        self.patch_jump(skip)?;
         self.emit_op_code(OpCodeKind::Pop);
         Ok(())
     }
 
+    fn try_statement(&mut self) -> VoidResult {
+        self.consume(TokenType::LeftBrace, "Expected '{' after 'try'".to_owned())?;
+        let try_handler = self.begin_handler(self.local_count());
+        self.begin_scope();
+        self.block()?;
+        self.end_scope();
+        let mut exits = self.end_handler();
+        self.emit_op_code(OpCodeKind::Null);
+        self.emit_op_code(OpCodeKind::False);
+        let try_end = self.emit_jump(OpCodeKind::Jump { offset: 0 });
+
+        // thrown value is on the stack in place of the first local after try
+        self.patch_jump(try_handler)?;
+        let has_catch = self.matches(&TokenType::CATCH)?;
+        if has_catch {
+            self.begin_scope();
+            self.consume(TokenType::LeftParen, "Expected '(' after 'catch'".to_owned())?;
+            self.consume(
+                TokenType::IDENTIFIER,
+                "Expected error variable name".to_owned(),
+            )?;
+            self.declare_variable()?;
+            self.mark_initialized();
+            self.consume(
+                TokenType::RightParen,
+                "Expected ')' after error variable".to_owned(),
+            )?;
+            self.consume(
+                TokenType::LeftBrace,
+                "Expected '{' after catch clause".to_owned(),
+            )?;
+
+            // error raised inside catch still has to pass through finally
+            let catch_handler = self.begin_handler(self.local_count() - 1);
+            self.begin_scope();
+            self.block()?;
+            self.end_scope();
+            exits.extend(self.end_handler());
+            self.end_scope();
+            self.emit_op_code(OpCodeKind::Null);
+            self.emit_op_code(OpCodeKind::False);
+            let catch_end = self.emit_jump(OpCodeKind::Jump { offset: 0 });
+            self.patch_jump(catch_handler)?;
+            self.emit_op_code(OpCodeKind::True);
+            self.patch_jump(catch_end)?;
+        } else {
+            self.emit_op_code(OpCodeKind::True);
+        }
+        self.patch_jump(try_end)?;
+        for &(_, jump) in &exits {
+            self.patch_jump(jump)?;
+        }
+
+        let has_finally = self.matches(&TokenType::FINALLY)?;
+        if !has_catch && !has_finally {
+            return Err(self.error("Expected 'catch' or 'finally' after try block".to_owned()));
+        }
+
+        // pending error and rethrow flag are hidden locals, so finally locals get right slots,
+        // own scope keeps them above the enclosing loop so leaving it discards them
+        self.begin_scope();
+        self.add_hidden_local();
+        self.add_hidden_local();
+        if has_finally {
+            self.consume(
+                TokenType::LeftBrace,
+                "Expected '{' after 'finally'".to_owned(),
+            )?;
+            self.begin_scope();
+            self.block()?;
+            self.end_scope();
+        }
+        let hidden_start = self.local_count() - 2;
+        for exit in [Exit::Break, Exit::Continue, Exit::Return] {
+            if exits.iter().any(|&(pending, _)| pending == exit) {
+                self.emit_pending_exit(exit, hidden_start)?;
+            }
+        }
+        // end finally pops both hidden locals
+        self.emit_op_code(OpCodeKind::EndFinally);
+        self.scope_mut().locals.truncate(hidden_start);
+        self.end_scope();
+        Ok(())
+    }
+
     fn for_statement(&mut self) -> VoidResult {
         self.begin_scope();
         self.consume(TokenType::LeftParen, "Expected '(' after for".to_owned())?;
@@ -746,11 +1747,21 @@ impl Compiler {
         if self.matches(&TokenType::SEMICOLON)? {
         } else if self.matches(&TokenType::VAR)? {
             self.var_statement()?;
+        } else if self.matches(&TokenType::IDENTIFIER)? {
+            let name = self.previous().unwrap().clone();
+            if self.matches(&TokenType::IN)? {
+                self.for_in_statement(name)?;
+                self.end_scope();
+                return Ok(());
+            }
+            // identifier starts initializer expression
+            self.named_variable(self.previous_string_literal()?, true)?;
+            self.finish_expr_statement()?;
         } else {
             self.expr_statement()?;
         }
 
-        let mut loop_start = self.current_chunk.as_ref().unwrap().borrow().len();
+        let mut loop_start = self.current_chunk().borrow().len();
         let mut exit_jump: Option<usize> = None;
 
         if !self.matches(&TokenType::SEMICOLON)? {
@@ -763,26 +1774,58 @@ impl Compiler {
 
         if !self.matches(&TokenType::RightParen)? {
             let body_jump = self.emit_jump(OpCodeKind::Jump { offset: 0 });
-            let increment_start = self.current_chunk.as_ref().unwrap().borrow().len();
+            let increment_start = self.current_chunk().borrow().len();
 
             self.expression()?;
             self.emit_op_code(OpCodeKind::Pop);
             self.consume(TokenType::RightParen, "Expected ')' after for clauses".to_owned())?;
 
-            self.emit_loop(loop_start);
+            self.emit_loop(loop_start)?;
             loop_start = increment_start;
-            self.patch_jump(body_jump);
+            self.patch_jump(body_jump)?;
         }
 
+        self.begin_loop(loop_start);
         self.statement()?;
-        self.emit_loop(loop_start);
+        self.emit_loop(loop_start)?;
 
-        if exit_jump.is_some() {
-            self.patch_jump(exit_jump.unwrap());
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump)?;
             self.emit_op_code(OpCodeKind::Pop);
         }
+        self.end_loop()?;
+
+        self.end_scope();
+        Ok(())
+    }
+
+    /// for (x in iterable), every iteration gets fresh ``x`` so closures capture its current value
+    fn for_in_statement(&mut self, name: Rc<Token>) -> VoidResult {
+        self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expected ')' after iterable".to_owned(),
+        )?;
+        self.emit_op_code(OpCodeKind::GetIterator);
+        self.add_hidden_local();
+        let iterator_slot = self.local_count() - 1;
+
+        let loop_start = self.current_chunk().borrow().len();
+        let exit_jump = self.emit_jump(OpCodeKind::ForIter {
+            slot: iterator_slot,
+            offset: 0,
+        });
+        self.begin_loop(loop_start);
 
+        self.begin_scope();
+        self.add_local(name);
+        self.mark_initialized();
+        self.statement()?;
         self.end_scope();
+        self.emit_loop(loop_start)?;
+
+        self.patch_jump(exit_jump)?;
+        self.end_loop()?;
         Ok(())
     }
 
@@ -807,7 +1850,20 @@ impl Compiler {
         if self.debug_mode {
             println!("Called number() for {value}");
         }
-        self.emit_const(rc_refcell!(value));
+        self.emit_const(value)?;
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn integer(&mut self, can_assign: bool) -> VoidResult {
+        let literal = self.previous().unwrap().literal.clone().unwrap();
+        let Ok(value) = literal.parse::<i64>() else {
+            return Err(self.error("Integer literal is too large".to_owned()));
+        };
+        if self.debug_mode {
+            println!("Called integer() for {value}");
+        }
+        self.emit_const(Value::Int(value))?;
         Ok(())
     }
 
@@ -830,12 +1886,46 @@ impl Compiler {
         if self.debug_mode {
             println!("Called string()");
         };
-        self.emit_const(rc_refcell!(Value::Object(Box::new(StringObject::new(
-            self.previous().unwrap().literal.clone().unwrap()
-        )))));
+        let literal = self.previous().unwrap().literal.clone().unwrap();
+        let string = self.heap.borrow_mut().alloc_string(&literal);
+        self.emit_const(Value::Object(string))?;
         Ok(())
     }
 
+    /// Compiles ``"a ${x} b"`` into segments and expressions joined into one string
+    #[allow(unused_variables)]
+    fn interpolation(&mut self, can_assign: bool) -> VoidResult {
+        let mut parts_count = 0;
+        loop {
+            self.string(false)?;
+            if self.is_empty_interpolation() {
+                return Err(self.error("Expected expression inside '${}'".to_owned()));
+            }
+            self.expression()?;
+            parts_count += 2;
+
+            if !self.matches(&TokenType::INTERPOLATION)? {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::STRING,
+            "Expected '}' after interpolated expression".to_owned(),
+        )?;
+        self.string(false)?;
+        parts_count += 1;
+
+        self.emit_op_code(OpCodeKind::BuildString { parts_count });
+        Ok(())
+    }
+
+    /// Rest of the string right after ``${`` means nothing was embedded, it starts with '}'
+    fn is_empty_interpolation(&self) -> bool {
+        let current = self.current().unwrap();
+        matches!(current.token_type, TokenType::STRING | TokenType::INTERPOLATION)
+            && self.scanner.substr(current.start, current.start + 1) == "}"
+    }
+
     #[allow(unused_variables)]
     fn grouping(&mut self, can_assign: bool) -> VoidResult {
         self.expression()?;
@@ -854,6 +1944,7 @@ impl Compiler {
         match op_type {
             TokenType::MINUS => self.emit_op_code(OpCodeKind::Negate),
             TokenType::BANG => self.emit_op_code(OpCodeKind::Not),
+            TokenType::TILDE => self.emit_op_code(OpCodeKind::BitNot),
             _ => unreachable!(),
         };
         Ok(())
@@ -874,7 +1965,11 @@ impl Compiler {
     fn binary(&mut self, can_assign: bool) -> VoidResult {
         let op_type = &self.previous().unwrap().token_type.clone();
         let rule = self.get_rule(op_type);
-        let next_precedence = self.next_precedence(rule.precedence);
+        let next_precedence = match op_type {
+            // right associative: 2 ** 3 ** 2 is 2 ** (3 ** 2)
+            TokenType::StarStar => rule.precedence,
+            _ => self.next_precedence(rule.precedence),
+        };
 
         if self.debug_mode {
             println!(
@@ -904,6 +1999,38 @@ impl Compiler {
                 self.emit_op_code(OpCodeKind::Mul);
                 Ok(())
             }
+            TokenType::PERCENT => {
+                self.emit_op_code(OpCodeKind::Mod);
+                Ok(())
+            }
+            TokenType::StarStar => {
+                self.emit_op_code(OpCodeKind::Pow);
+                Ok(())
+            }
+            TokenType::TildeSlash => {
+                self.emit_op_code(OpCodeKind::FloorDiv);
+                Ok(())
+            }
+            TokenType::AMPERSAND => {
+                self.emit_op_code(OpCodeKind::BitAnd);
+                Ok(())
+            }
+            TokenType::PIPE => {
+                self.emit_op_code(OpCodeKind::BitOr);
+                Ok(())
+            }
+            TokenType::CARET => {
+                self.emit_op_code(OpCodeKind::BitXor);
+                Ok(())
+            }
+            TokenType::LessLess => {
+                self.emit_op_code(OpCodeKind::ShiftLeft);
+                Ok(())
+            }
+            TokenType::GreaterGreater => {
+                self.emit_op_code(OpCodeKind::ShiftRight);
+                Ok(())
+            }
             TokenType::BangEqual => {
                 self.emit_op_code(OpCodeKind::Eq);
                 self.emit_op_code(OpCodeKind::Not);
@@ -931,10 +2058,142 @@ impl Compiler {
                 self.emit_op_code(OpCodeKind::Not);
                 Ok(())
             }
+            TokenType::IN => {
+                self.emit_op_code(OpCodeKind::Contains);
+                Ok(())
+            }
             _ => unreachable!(),
         }
     }
 
+    #[allow(unused_variables)]
+    fn call(&mut self, can_assign: bool) -> VoidResult {
+        let args_count = self.argument_list()?;
+        self.emit_op_code(OpCodeKind::Call { args_count });
+        Ok(())
+    }
+
+    fn dot(&mut self, can_assign: bool) -> VoidResult {
+        self.consume(
+            TokenType::IDENTIFIER,
+            "Expected property name after '.'".to_owned(),
+        )?;
+        let name_idx = self.identifier_constant(self.previous_string_literal()?)?;
+
+        if can_assign && self.matches(&TokenType::EQUAL)? {
+            self.expression()?;
+            self.emit_op_code(OpCodeKind::SetProperty { name_idx });
+        } else {
+            self.emit_op_code(OpCodeKind::GetProperty { name_idx });
+        }
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn this(&mut self, can_assign: bool) -> VoidResult {
+        if self.classes.is_empty() {
+            return Err(self.error("Cannot use 'this' outside of a class".to_owned()));
+        }
+        self.named_variable(self.previous().unwrap().literal.clone().unwrap(), false)
+    }
+
+    #[allow(unused_variables)]
+    fn super_(&mut self, can_assign: bool) -> VoidResult {
+        match self.classes.last() {
+            None => return Err(self.error("Cannot use 'super' outside of a class".to_owned())),
+            Some(class) if !class.has_superclass => {
+                return Err(
+                    self.error("Cannot use 'super' in a class with no superclass".to_owned())
+                );
+            }
+            Some(_) => {}
+        }
+
+        self.consume(TokenType::DOT, "Expected '.' after 'super'".to_owned())?;
+        self.consume(
+            TokenType::IDENTIFIER,
+            "Expected superclass method name".to_owned(),
+        )?;
+        let name_idx = self.identifier_constant(self.previous_string_literal()?)?;
+
+        self.named_variable(Rc::new("this".to_owned()), false)?;
+        self.named_variable(Rc::new("super".to_owned()), false)?;
+        self.emit_op_code(OpCodeKind::GetSuper { name_idx });
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn list(&mut self, can_assign: bool) -> VoidResult {
+        let mut items_count = 0;
+        while !self.check(&TokenType::RightBracket) {
+            self.expression()?;
+            items_count += 1;
+            if !self.matches(&TokenType::COMMA)? {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RightBracket,
+            "Expected ']' after list items".to_owned(),
+        )?;
+        self.emit_op_code(OpCodeKind::BuildList { items_count });
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn map(&mut self, can_assign: bool) -> VoidResult {
+        let mut entries_count = 0;
+        while !self.check(&TokenType::RightBrace) {
+            self.expression()?;
+            self.consume(TokenType::COLON, "Expected ':' after map key".to_owned())?;
+            self.expression()?;
+            entries_count += 1;
+            if !self.matches(&TokenType::COMMA)? {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expected '}' after map entries".to_owned(),
+        )?;
+        self.emit_op_code(OpCodeKind::BuildMap { entries_count });
+        Ok(())
+    }
+
+    fn index(&mut self, can_assign: bool) -> VoidResult {
+        self.expression()?;
+        self.consume(TokenType::RightBracket, "Expected ']' after index".to_owned())?;
+
+        if can_assign && self.matches(&TokenType::EQUAL)? {
+            self.expression()?;
+            self.emit_op_code(OpCodeKind::SetIndex);
+        } else {
+            self.emit_op_code(OpCodeKind::GetIndex);
+        }
+        Ok(())
+    }
+
+    fn argument_list(&mut self) -> Result<usize, Error> {
+        let mut args_count = 0;
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                self.expression()?;
+                if args_count == MAX_ARGS {
+                    return Err(self.error(format!("Cannot have more than {MAX_ARGS} arguments")));
+                }
+                args_count += 1;
+                if !self.matches(&TokenType::COMMA)? {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expected ')' after arguments".to_owned(),
+        )?;
+        Ok(args_count)
+    }
+
     #[allow(unused_variables)]
     fn and(&mut self, can_assign: bool) -> VoidResult {
         let end_jump = self.emit_jump(OpCodeKind::JumpIfFalse { offset: 0 });
@@ -942,7 +2201,28 @@ impl Compiler {
 
         self.parse_precedence(And)?;
 
-        self.patch_jump(end_jump);
+        self.patch_jump(end_jump)?;
+        Ok(())
+    }
+
+    /// cond ? a : b, right associative so that a ? b : c ? d : e nests in else branch
+    #[allow(unused_variables)]
+    fn ternary(&mut self, can_assign: bool) -> VoidResult {
+        let else_jump = self.emit_jump(OpCodeKind::JumpIfFalse { offset: 0 });
+        self.emit_op_code(OpCodeKind::Pop);
+
+        self.parse_precedence(Ternary)?;
+        let end_jump = self.emit_jump(OpCodeKind::Jump { offset: 0 });
+        self.consume(
+            TokenType::COLON,
+            "Expected ':' after then branch of conditional expression".to_owned(),
+        )?;
+
+        self.patch_jump(else_jump)?;
+        self.emit_op_code(OpCodeKind::Pop);
+
+        self.parse_precedence(Ternary)?;
+        self.patch_jump(end_jump)?;
         Ok(())
     }
 
@@ -951,11 +2231,11 @@ impl Compiler {
         let else_jump = self.emit_jump(OpCodeKind::JumpIfFalse { offset: 0 });
         let end_jump = self.emit_jump(OpCodeKind::Jump { offset: 0 });
 
-        self.patch_jump(else_jump);
+        self.patch_jump(else_jump)?;
         self.emit_op_code(OpCodeKind::Pop);
 
         self.parse_precedence(Or)?;
-        self.patch_jump(end_jump);
+        self.patch_jump(end_jump)?;
         Ok(())
     }
 
@@ -974,7 +2254,11 @@ impl Compiler {
 
         let can_assign = precedence as usize <= Assignment as usize;
         prefix_rule(self, can_assign)?;
+        self.parse_infix(precedence, can_assign)
+    }
 
+    /// Continues parsing expression whose prefix is already compiled
+    fn parse_infix(&mut self, precedence: Precedence, can_assign: bool) -> VoidResult {
         let current_token_precedence = self
             .get_rule(&self.current().unwrap().token_type)
             .precedence as usize;
@@ -1019,7 +2303,9 @@ impl Compiler {
             infix_rule(self, can_assign)?;
         };
 
-        if can_assign && self.matches(&TokenType::EQUAL)? {
+        if can_assign
+            && (self.matches(&TokenType::EQUAL)? || self.compound_assignment(true)?.is_some())
+        {
             Err(self.error("Invalid assignment target".to_owned()))
         } else {
             Ok(())
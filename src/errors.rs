@@ -1,3 +1,5 @@
+use std::{fmt::Display, path::PathBuf};
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,8 +13,71 @@ pub enum RuntimeErrorKind {
     #[error("AlreadyDefinedVariableError: name '{name}' is already defined")]
     AlreadyDefinedVariable { name: String },
 
+    #[error("TypeError: cannot assign to constant '{name}'")]
+    ConstantAssignment { name: String },
+
     #[error("TypeError: expected {expected}, got {provided}")]
     TypeError { expected: String, provided: String },
+
+    #[error("TypeError: {type_name} object is not callable")]
+    NotCallable { type_name: String },
+
+    #[error("TypeError: {name} expected {expected} arguments, got {provided}")]
+    ArgumentsCount {
+        name: String,
+        expected: usize,
+        provided: usize,
+    },
+
+    #[error("TypeError: {name} expected {min} to {max} arguments, got {provided}")]
+    ArgumentsCountRange {
+        name: String,
+        min: usize,
+        max: usize,
+        provided: usize,
+    },
+
+    #[error("AttributeError: {type_name} object has no attribute '{name}'")]
+    UndefinedAttribute { type_name: String, name: String },
+
+    #[error("TypeError: superclass must be a class, got {type_name}")]
+    InvalidSuperclass { type_name: String },
+
+    #[error("AttributeError: superclass {class_name} has no method '{name}'")]
+    UndefinedSuperMethod { class_name: String, name: String },
+
+    #[error("IndexError: index {index} is out of range for length {length}")]
+    IndexOutOfRange { index: String, length: usize },
+
+    #[error("KeyError: key {key} is not found")]
+    KeyNotFound { key: String },
+
+    #[error("TypeError: unhashable type {type_name}")]
+    Unhashable { type_name: String },
+
+    #[error("ZeroDivisionError: {op} by zero")]
+    ZeroDivision { op: String },
+
+    #[error("OverflowError: result of {expression} does not fit into int")]
+    IntegerOverflow { expression: String },
+
+    #[error("OverflowError: result of {expression} is too large for float")]
+    FloatOverflow { expression: String },
+
+    #[error("ValueError: {message}")]
+    InvalidValue { message: String },
+
+    #[error("ImportError: cannot import '{path}', {reason}")]
+    ImportFailed { path: String, reason: String },
+
+    #[error("{message}")]
+    Thrown { message: String },
+
+    #[error("RecursionError: maximum call depth of {max_depth} exceeded")]
+    StackOverflow { max_depth: usize },
+
+    #[error("RecursionError: maximum comparison depth of {max_depth} exceeded")]
+    ComparisonDepth { max_depth: usize },
 }
 
 #[derive(Error, Debug)]
@@ -20,8 +85,23 @@ pub enum RuntimeErrorKind {
 pub struct ParsingError {}
 
 #[derive(Debug, Error)]
-#[error("[line {line}] Runtime error:\n{kind}")]
 pub struct RuntimeError {
     pub kind: RuntimeErrorKind,
     pub line: usize,
+    pub module: Option<PathBuf>, // file of imported module the line belongs to, none for main script
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.module {
+            Some(path) => write!(
+                f,
+                "[{}, line {}] Runtime error:\n{}",
+                path.display(),
+                self.line,
+                self.kind
+            ),
+            None => write!(f, "[line {}] Runtime error:\n{}", self.line, self.kind),
+        }
+    }
 }
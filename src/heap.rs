@@ -0,0 +1,202 @@
+use std::{
+    cell::Cell,
+    fmt::{Debug, Display},
+    ops::Deref,
+    ptr::NonNull,
+};
+
+use crate::{
+    alias::DynObject,
+    interner::{Interner, Symbol},
+    object::{Object, string::StringObject},
+    value::Value,
+};
+
+/// Live objects count which triggers the first collection
+pub const DEFAULT_GC_THRESHOLD: usize = 1 << 14;
+const GC_GROWTH_FACTOR: usize = 2;
+
+/// Object allocated on the managed heap together with its mark bit
+struct GcBox {
+    is_marked: Cell<bool>,
+    object: DynObject,
+}
+
+/// Handle of an object owned by the ``Heap``, copying a handle never copies the object.
+///
+/// Handle stays valid as long as the object is reachable from the roots of the VM,
+/// collections run only between instructions, so handles held during an instruction are never freed
+#[derive(Clone, Copy)]
+pub struct ObjRef(NonNull<GcBox>);
+
+impl ObjRef {
+    /// Identity comparison, true when both handles point to the same object
+    pub fn ptr_eq(&self, other: &ObjRef) -> bool {
+        self.0 == other.0
+    }
+
+    /// Address of the object, used to encode the handle into a NaN-boxed value
+    #[cfg(feature = "nan-boxing")]
+    pub fn to_bits(self) -> u64 {
+        self.0.as_ptr().expose_provenance() as u64
+    }
+
+    /// # Safety
+    /// ``bits`` must be returned by ``to_bits`` of a handle whose object is still alive
+    #[cfg(feature = "nan-boxing")]
+    pub unsafe fn from_bits(bits: u64) -> Self {
+        let ptr = std::ptr::with_exposed_provenance_mut::<GcBox>(bits as usize);
+        // SAFETY: address of a live object is never null
+        Self(unsafe { NonNull::new_unchecked(ptr) })
+    }
+
+    fn gc_box(&self) -> &GcBox {
+        // SAFETY: boxes are freed only by the sweep, which never frees reachable objects
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Deref for ObjRef {
+    type Target = dyn Object;
+
+    fn deref(&self) -> &Self::Target {
+        self.gc_box().object.as_ref()
+    }
+}
+
+impl AsRef<dyn Object> for ObjRef {
+    fn as_ref(&self) -> &(dyn Object + 'static) {
+        self.gc_box().object.as_ref()
+    }
+}
+
+impl Display for ObjRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.gc_box().object)
+    }
+}
+
+impl Debug for ObjRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.gc_box().object)
+    }
+}
+
+/// Mark phase state, objects are marked once and queued to have their references traced
+pub struct Tracer {
+    gray: Vec<ObjRef>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self { gray: vec![] }
+    }
+
+    pub fn mark(&mut self, obj: ObjRef) {
+        if !obj.gc_box().is_marked.replace(true) {
+            self.gray.push(obj);
+        }
+    }
+
+    pub fn mark_value(&mut self, value: &Value) {
+        if let Value::Object(obj) = value {
+            self.mark(*obj);
+        }
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Owner of every object created by scripts, freed by the tracing mark-and-sweep collector
+pub struct Heap {
+    objects: Vec<NonNull<GcBox>>,
+    threshold: usize,
+    next_gc: usize,
+    allocated: usize, // allocations since the last collection
+    stress: bool,     // collect at every safe point following an allocation
+    strings: Interner,
+}
+
+impl Heap {
+    pub fn new(threshold: usize, stress: bool) -> Self {
+        Self {
+            objects: vec![],
+            threshold,
+            next_gc: threshold,
+            allocated: 0,
+            stress,
+            strings: Interner::new(),
+        }
+    }
+
+    pub fn intern(&mut self, value: &str) -> Symbol {
+        self.strings.intern(value)
+    }
+
+    /// String object backed by the interned ``value``
+    pub fn alloc_string(&mut self, value: &str) -> ObjRef {
+        let symbol = self.intern(value);
+        self.alloc(StringObject::new(symbol))
+    }
+
+    pub fn alloc(&mut self, object: impl Object) -> ObjRef {
+        let gc_box = Box::new(GcBox {
+            is_marked: Cell::new(false),
+            object: Box::new(object),
+        });
+        let ptr = NonNull::from(Box::leak(gc_box));
+        self.objects.push(ptr);
+        self.allocated += 1;
+        ObjRef(ptr)
+    }
+
+    pub fn objects_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn should_collect(&self) -> bool {
+        if self.stress {
+            self.allocated > 0
+        } else {
+            self.objects.len() >= self.next_gc
+        }
+    }
+
+    /// Traces everything reachable from the roots marked by ``tracer`` and frees the rest,
+    /// returns count of freed objects
+    pub fn collect(&mut self, mut tracer: Tracer) -> usize {
+        while let Some(obj) = tracer.gray.pop() {
+            obj.trace(&mut tracer);
+        }
+
+        let before = self.objects.len();
+        self.objects.retain(|ptr| {
+            // SAFETY: every pointer in ``objects`` comes from ``Box::leak`` and is freed only here
+            let gc_box = unsafe { ptr.as_ref() };
+            if gc_box.is_marked.replace(false) {
+                return true;
+            }
+            drop(unsafe { Box::from_raw(ptr.as_ptr()) });
+            false
+        });
+
+        // strings of freed objects are gone unless a constant or a name still holds them
+        self.strings.prune();
+        self.allocated = 0;
+        self.next_gc = self.threshold.max(self.objects.len() * GC_GROWTH_FACTOR);
+        before - self.objects.len()
+    }
+}
+
+impl Drop for Heap {
+    fn drop(&mut self) {
+        for ptr in self.objects.drain(..) {
+            // SAFETY: heap owns all of its objects, no handle is used after the heap is gone
+            drop(unsafe { Box::from_raw(ptr.as_ptr()) });
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+/// Interned string, equal contents always share one allocation,
+/// so equality and hashing look only at the pointer
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(Rc::as_ptr(&self.0).cast::<u8>() as usize);
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+/// Table of every string known to the VM, shared by the compiler and the runtime
+pub struct Interner {
+    strings: HashSet<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self {
+            strings: HashSet::new(),
+        }
+    }
+
+    /// Symbol for ``value``, allocated only the first time the contents are seen
+    pub fn intern(&mut self, value: &str) -> Symbol {
+        if let Some(string) = self.strings.get(value) {
+            return Symbol(string.clone());
+        }
+        let string: Rc<str> = Rc::from(value);
+        self.strings.insert(string.clone());
+        Symbol(string)
+    }
+
+    /// Forgets strings no symbol refers to anymore
+    pub fn prune(&mut self) {
+        self.strings.retain(|string| Rc::strong_count(string) > 1);
+    }
+}
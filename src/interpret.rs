@@ -5,7 +5,7 @@ use anyhow::Error;
 use crate::{alias::StoredChunk, compiler::Compiler, vm::VirtualMachine};
 
 pub fn interpret(source: String, chunk: StoredChunk, vm: &mut VirtualMachine, debug: bool) -> Result<(), Error> {
-    let mut compiler = Compiler::from_source(source, debug);
+    let mut compiler = Compiler::from_source(source, vm.main_globals(), vm.heap(), debug);
 
     if debug {
         println!("Compiling...");
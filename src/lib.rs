@@ -0,0 +1,21 @@
+//! Lox interpreter, the ``rlox`` binary and the benchmarks are built on top of it
+
+pub mod alias;
+mod bin_op;
+pub mod chunk;
+pub mod compiler;
+mod errors;
+pub mod heap;
+mod interner;
+pub mod interpret;
+mod macros;
+mod modules;
+pub mod namespace;
+pub mod natives;
+mod object;
+mod parser;
+mod scanner;
+mod stack_value;
+mod token;
+mod value;
+pub mod vm;
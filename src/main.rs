@@ -1,26 +1,20 @@
 use std::{
     fs::File,
     io::{self, Read},
+    path::PathBuf,
 };
 
-mod alias;
-mod bin_op;
-mod chunk;
-mod compiler;
-mod errors;
-mod interpret;
-mod macros;
-mod namespace;
-mod object;
-mod parser;
-mod scanner;
-mod token;
-mod value;
-mod vm;
-
-use crate::{chunk::Chunk, interpret::interpret, namespace::NameSpace, vm::VirtualMachine};
 use anyhow::Error;
 use clap::Parser as CliParser;
+use rlox::{
+    chunk::Chunk,
+    heap::{DEFAULT_GC_THRESHOLD, Heap},
+    interpret::interpret,
+    namespace::NameSpace,
+    natives,
+    rc_refcell,
+    vm::VirtualMachine,
+};
 
 const VERSION: &str = "0.0.1";
 
@@ -36,6 +30,12 @@ struct CliArgs {
     file_name: Option<String>,
     #[arg(short, long, default_value_t = false)]
     debug: bool,
+    /// Run garbage collection after every allocation, slow, for testing the collector
+    #[arg(long, default_value_t = false)]
+    gc_stress: bool,
+    /// Count of live objects which triggers the first garbage collection
+    #[arg(long, default_value_t = DEFAULT_GC_THRESHOLD)]
+    gc_threshold: usize,
 }
 
 fn read_file_to_string(file_name: &str) -> String {
@@ -47,13 +47,14 @@ fn read_file_to_string(file_name: &str) -> String {
     buf
 }
 
-fn repl(debug: bool) {
+fn repl(heap: Heap, debug: bool) {
     println!("Running RLox, mode: REPL, author: lubaskinc0de, current version: {VERSION}");
     println!("Enter program code:");
 
-    let mut globals = NameSpace::new();
+    let globals = rc_refcell!(NameSpace::new());
     let chunk = rc_refcell!(Chunk::new());
-    let mut vm = VirtualMachine::new(chunk.clone(), &mut globals, debug);
+    let mut vm = VirtualMachine::new(chunk.clone(), globals, rc_refcell!(heap), debug);
+    natives::define_builtins(&mut vm);
     loop {
         eprint!("> ");
         let mut prompt = String::new();
@@ -66,10 +67,12 @@ fn repl(debug: bool) {
     }
 }
 
-fn run_source(content: String, debug: bool) -> Result<(), Error> {
-    let mut globals = NameSpace::new();
+fn run_source(content: String, file_name: &str, heap: Heap, debug: bool) -> Result<(), Error> {
+    // imports are resolved relative to the script file
+    let globals = rc_refcell!(NameSpace::from_file(PathBuf::from(file_name)));
     let chunk = rc_refcell!(Chunk::new());
-    let mut vm = VirtualMachine::new(chunk.clone(), &mut globals, debug);
+    let mut vm = VirtualMachine::new(chunk.clone(), globals, rc_refcell!(heap), debug);
+    natives::define_builtins(&mut vm);
     interpret(content, chunk, &mut vm, debug)
 }
 
@@ -77,16 +80,17 @@ fn main() {
     let cli = CliArgs::parse();
     let file_name = cli.file_name;
     let debug = cli.debug;
+    let heap = Heap::new(cli.gc_threshold, cli.gc_stress);
 
     let result = match (cli.repl, file_name) {
         (true, None) => {
-            repl(debug);
+            repl(heap, debug);
             Ok(())
         }
         (false, None) => panic!("Pass the file name or run in REPL mode"),
         (false, Some(filename)) | (true, Some(filename)) => {
             let content = read_file_to_string(&filename);
-            run_source(content, debug)
+            run_source(content, &filename, heap, debug)
         }
     };
 
@@ -0,0 +1,25 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Environment variable with additional directories searched for imported files
+pub const PATH_ENV: &str = "RLOX_PATH";
+
+/// Finds imported file in the importing file's directory first, then in ``RLOX_PATH`` directories
+pub fn resolve(import_path: &str, importer: Option<&Path>) -> Option<PathBuf> {
+    let importer_dir = match importer {
+        Some(importer) => importer.parent().map(Path::to_path_buf),
+        None => env::current_dir().ok(), // REPL has no file, imports are relative to cwd
+    };
+    let search_path: Vec<PathBuf> = env::var_os(PATH_ENV)
+        .map(|value| env::split_paths(&value).collect())
+        .unwrap_or_default();
+
+    importer_dir
+        .into_iter()
+        .chain(search_path)
+        .map(|dir| dir.join(import_path))
+        .find(|candidate| candidate.is_file())
+        .and_then(|found| found.canonicalize().ok())
+}
@@ -1,26 +1,122 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use crate::alias::StoredValue;
+use crate::{heap::Tracer, interner::Symbol, value::Value};
 
-type K = Rc<String>;
-type V = StoredValue;
+type K = Symbol;
+type V = Value;
 
+/// Global variable slot, reserved by the compiler at the first mention of the name
+#[derive(Clone)]
+struct Global {
+    name: K,
+    value: Option<V>, // none until the definition is executed
+    is_const: bool,
+}
+
+/// Globals of a module, stored in dense slots resolved at compile time,
+/// names are kept for error messages and attribute access
+#[derive(Clone)]
 pub struct NameSpace {
-    table: HashMap<K, V>,
+    slots: Vec<Global>,
+    indices: HashMap<K, usize>,
+    exports: HashSet<K>,
+    path: Option<PathBuf>, // file the globals belong to, none for REPL
 }
 
 impl NameSpace {
     pub fn new() -> Self {
         Self {
-            table: HashMap::new(),
+            slots: vec![],
+            indices: HashMap::new(),
+            exports: HashSet::new(),
+            path: None,
+        }
+    }
+
+    pub fn from_file(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            ..Self::new()
+        }
+    }
+
+    /// Slot of the global, undefined slot is reserved if the name is seen for the first time
+    pub fn resolve(&mut self, key: K) -> usize {
+        if let Some(slot) = self.indices.get(&key) {
+            return *slot;
         }
+        self.slots.push(Global {
+            name: key.clone(),
+            value: None,
+            is_const: false,
+        });
+        self.indices.insert(key, self.slots.len() - 1);
+        self.slots.len() - 1
+    }
+
+    pub fn name(&self, slot: usize) -> &K {
+        &self.slots[slot].name
     }
 
     pub fn insert(&mut self, key: K, value: V) {
-        self.table.insert(key, value);
+        let slot = self.resolve(key);
+        self.set(slot, value);
+    }
+
+    pub fn get(&self, key: &K) -> Option<Value> {
+        self.indices.get(key).and_then(|slot| self.read(*slot))
+    }
+
+    pub fn read(&self, slot: usize) -> Option<Value> {
+        self.slots[slot].value.clone()
+    }
+
+    pub fn set(&mut self, slot: usize, value: V) {
+        self.slots[slot].value = Some(value);
     }
 
-    pub fn get(&self, key: &K) -> Option<StoredValue> {
-        self.table.get(key).cloned()
+    /// Defines global, read-only if ``is_const``
+    pub fn define(&mut self, slot: usize, value: V, is_const: bool) {
+        self.slots[slot].is_const = is_const;
+        self.set(slot, value);
+    }
+
+    pub fn is_defined(&self, slot: usize) -> bool {
+        self.slots[slot].value.is_some()
+    }
+
+    pub fn is_const(&self, slot: usize) -> bool {
+        self.slots[slot].is_const
+    }
+
+    /// Makes global visible to modules importing this one
+    pub fn export(&mut self, key: K) {
+        self.exports.insert(key);
+    }
+
+    pub fn get_exported(&self, key: &K) -> Option<Value> {
+        if !self.exports.contains(key) {
+            return None;
+        }
+        self.get(key)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn trace(&self, tracer: &mut Tracer) {
+        for value in self.slots.iter().filter_map(|global| global.value.as_ref()) {
+            tracer.mark_value(value);
+        }
+    }
+}
+
+impl Default for NameSpace {
+    fn default() -> Self {
+        Self::new()
     }
 }
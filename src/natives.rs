@@ -0,0 +1,80 @@
+use std::{
+    any::Any,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    isinstance,
+    object::{ResultRE, list::ListObject, map::MapObject, range::RangeObject},
+    value::Value,
+    vm::VirtualMachine,
+};
+
+/// Registers builtins available to every script
+pub fn define_builtins(vm: &mut VirtualMachine) {
+    vm.define_native("clock", 0..=0, clock);
+    vm.define_native("len", 1..=1, len);
+    vm.define_native("keys", 1..=1, keys);
+    vm.define_native("range", 1..=3, range);
+}
+
+/// Seconds elapsed since the unix epoch
+fn clock(_vm: &mut VirtualMachine, _args: &[Value]) -> ResultRE<Value> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch");
+    Ok(Value::Float(elapsed.as_secs_f64()))
+}
+
+/// Length of a collection or a string
+fn len(_vm: &mut VirtualMachine, args: &[Value]) -> ResultRE<Value> {
+    let length = match &args[0] {
+        Value::Object(obj) => obj.len()?,
+        other => {
+            return Err(RuntimeErrorKind::OperationNotSupported {
+                target: format!("for {}", other.type_name()),
+                op: "len".to_owned(),
+            });
+        }
+    };
+    Ok(Value::Int(length as i64))
+}
+
+/// List of map keys in insertion order
+fn keys(vm: &mut VirtualMachine, args: &[Value]) -> ResultRE<Value> {
+    let not_map = || RuntimeErrorKind::TypeError {
+        expected: "map".to_owned(),
+        provided: args[0].type_name(),
+    };
+    let Value::Object(obj) = &args[0] else {
+        return Err(not_map());
+    };
+    if !isinstance!(obj, MapObject) {
+        return Err(not_map());
+    }
+    let map = cast!(obj => MapObject)?;
+    let keys = map.entries.borrow().keys();
+    Ok(vm.allocate(ListObject::new(keys)))
+}
+
+/// Integers from start up to, but not including, stop,
+/// called as ``range(stop)``, ``range(start, stop)`` or ``range(start, stop, step)``
+fn range(vm: &mut VirtualMachine, args: &[Value]) -> ResultRE<Value> {
+    let mut bounds = Vec::with_capacity(args.len());
+    for arg in args {
+        bounds.push(arg.as_int().ok_or_else(|| RuntimeErrorKind::TypeError {
+            expected: "int".to_owned(),
+            provided: arg.type_name(),
+        })?);
+    }
+    let (start, stop, step) = match bounds[..] {
+        [stop] => (0, stop, 1),
+        [start, stop] => (start, stop, 1),
+        [start, stop, step] => (start, stop, step),
+        _ => unreachable!("arity is checked by the VM"),
+    };
+    let range = RangeObject::new(start, stop, step)?;
+    Ok(vm.allocate(range))
+}
@@ -4,11 +4,25 @@ use std::{
 };
 
 use crate::{
-    alias::{DynObject, StoredValue},
     errors::RuntimeErrorKind,
-    value::Compare,
+    heap::{Heap, ObjRef, Tracer},
+    interner::Symbol,
+    value::{Compare, Value},
 };
 
+pub mod bound_method;
+pub mod class;
+pub mod closure;
+pub mod error;
+pub mod function;
+pub mod instance;
+pub mod iterator;
+pub mod list;
+pub mod map;
+pub mod module;
+pub mod native;
+pub mod range;
+pub mod recursion;
 pub mod string;
 
 pub type ResultRE<T> = Result<T, RuntimeErrorKind>; // result runtime error
@@ -16,26 +30,94 @@ pub type ResultRE<T> = Result<T, RuntimeErrorKind>; // result runtime error
 pub trait Object: Debug + Display + Any {
     fn type_name(&self) -> String;
 
-    #[allow(unused_variables, dead_code)]
-    fn get_attribute(&self, attr_name: &str) -> Option<StoredValue> {
+    #[allow(unused_variables)]
+    fn get_attribute(&self, attr_name: &Symbol) -> Option<Value> {
         None
     }
 
-    fn copy(&self) -> DynObject;
+    #[allow(unused_variables)]
+    fn set_attribute(&self, attr_name: &Symbol, value: Value) -> ResultRE<()> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "attribute assignment".to_owned(),
+        })
+    }
 
     #[allow(unused_variables)]
-    fn cmp(&self, other: &DynObject) -> ResultRE<Compare> {
+    fn get_item(&self, index: &Value) -> ResultRE<Value> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "indexing".to_owned(),
+        })
+    }
+
+    #[allow(unused_variables)]
+    fn set_item(&self, index: &Value, value: Value) -> ResultRE<()> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "item assignment".to_owned(),
+        })
+    }
+
+    #[allow(unused_variables)]
+    fn contains(&self, item: &Value) -> ResultRE<bool> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "in".to_owned(),
+        })
+    }
+
+    fn hash_code(&self) -> ResultRE<u64> {
+        Err(RuntimeErrorKind::Unhashable {
+            type_name: self.type_name(),
+        })
+    }
+
+    fn len(&self) -> ResultRE<usize> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "len".to_owned(),
+        })
+    }
+
+    /// Iterator driving ``for (x in object)``, object types opt in by overriding it,
+    /// ``this`` is the handle of the object itself
+    #[allow(unused_variables)]
+    fn iterate(&self, this: ObjRef, heap: &mut Heap) -> ResultRE<ObjRef> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "iteration".to_owned(),
+        })
+    }
+
+    /// Next item of an iterator, ``None`` once it is exhausted
+    #[allow(unused_variables)]
+    fn next_item(&self, heap: &mut Heap) -> ResultRE<Option<Value>> {
+        Err(RuntimeErrorKind::OperationNotSupported {
+            target: format!("for {}", self.type_name()),
+            op: "next".to_owned(),
+        })
+    }
+
+    /// Marks objects referenced by this one, so the collector keeps them alive
+    #[allow(unused_variables)]
+    fn trace(&self, tracer: &mut Tracer) {}
+
+    /// Equality beyond identity, handles of the same object are always equal
+    #[allow(unused_variables)]
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
         Ok(Compare::NotEqual)
     }
 
-    fn operation_not_supported(&self, other: &DynObject, op: String) -> RuntimeErrorKind {
+    fn operation_not_supported(&self, other: ObjRef, op: String) -> RuntimeErrorKind {
         RuntimeErrorKind::OperationNotSupported {
             target: format!("between {} and {}", self.type_name(), other.type_name()),
             op,
         }
     }
 
-    fn add(&self, other: &DynObject) -> ResultRE<StoredValue> {
+    #[allow(unused_variables)]
+    fn add(&self, other: ObjRef, heap: &mut Heap) -> ResultRE<Value> {
         Err(self.operation_not_supported(other, "+".to_owned()))
     }
 }
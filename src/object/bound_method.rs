@@ -0,0 +1,57 @@
+use std::{any::Any, fmt::Display};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    heap::{ObjRef, Tracer},
+    isinstance,
+    object::{Object, ResultRE, function::FUNCTION_TYPE},
+    value::{Compare, Value},
+};
+
+/// Method taken from an instance, remembers the instance to bind ``this`` on call
+pub struct BoundMethodObject {
+    pub receiver: Value,
+    pub method: ObjRef, // closure
+}
+
+impl BoundMethodObject {
+    pub fn new(receiver: Value, method: ObjRef) -> Self {
+        Self { receiver, method }
+    }
+}
+
+impl Display for BoundMethodObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.method)
+    }
+}
+
+impl std::fmt::Debug for BoundMethodObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for BoundMethodObject {
+    fn type_name(&self) -> String {
+        String::from(FUNCTION_TYPE)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark_value(&self.receiver);
+        tracer.mark(self.method);
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, BoundMethodObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_bound = cast!(other => BoundMethodObject)?;
+        if as_bound.receiver == self.receiver && as_bound.method.ptr_eq(&self.method) {
+            Ok(Compare::Equal)
+        } else {
+            Ok(Compare::NotEqual)
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display};
+
+use crate::{
+    heap::{ObjRef, Tracer},
+    object::Object,
+    interner::Symbol,
+};
+
+pub const CLASS_TYPE: &str = "class";
+
+/// Class declaration, methods are closures
+pub struct ClassObject {
+    pub name: Symbol,
+    pub methods: RefCell<HashMap<Symbol, ObjRef>>,
+}
+
+impl ClassObject {
+    pub fn new(name: Symbol) -> Self {
+        Self {
+            name,
+            methods: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_method(&self, name: Symbol, method: ObjRef) {
+        self.methods.borrow_mut().insert(name, method);
+    }
+
+    pub fn find_method(&self, name: &Symbol) -> Option<ObjRef> {
+        self.methods.borrow().get(name).copied()
+    }
+}
+
+impl Display for ClassObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+impl std::fmt::Debug for ClassObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for ClassObject {
+    fn type_name(&self) -> String {
+        String::from(CLASS_TYPE)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        for method in self.methods.borrow().values() {
+            tracer.mark(*method);
+        }
+    }
+}
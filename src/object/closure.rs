@@ -0,0 +1,81 @@
+use std::{any::Any, fmt::Display, rc::Rc};
+
+use crate::{
+    alias::StoredUpvalue,
+    cast,
+    errors::RuntimeErrorKind,
+    heap::{ObjRef, Tracer},
+    isinstance,
+    object::{Object, ResultRE, function::FUNCTION_TYPE},
+    value::{Compare, Value},
+};
+
+/// Variable captured by closure
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize), // variable still lives on the value stack at this index
+    Closed(Value),
+}
+
+/// Function together with variables it captured from enclosing scopes and module it was created in
+pub struct ClosureObject {
+    pub function: ObjRef,
+    pub upvalues: Vec<StoredUpvalue>,
+    pub module: ObjRef, // globals of the module are the globals of the closure
+}
+
+impl ClosureObject {
+    pub fn new(function: ObjRef, upvalues: Vec<StoredUpvalue>, module: ObjRef) -> Self {
+        Self {
+            function,
+            upvalues,
+            module,
+        }
+    }
+}
+
+impl Display for ClosureObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+impl std::fmt::Debug for ClosureObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for ClosureObject {
+    fn type_name(&self) -> String {
+        String::from(FUNCTION_TYPE)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark(self.function);
+        tracer.mark(self.module);
+        for upvalue in &self.upvalues {
+            if let Upvalue::Closed(value) = &*upvalue.borrow() {
+                tracer.mark_value(value);
+            }
+        }
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, ClosureObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_closure = cast!(other => ClosureObject)?;
+        let same_upvalues = self
+            .upvalues
+            .iter()
+            .zip(as_closure.upvalues.iter())
+            .all(|(a, b)| Rc::ptr_eq(a, b));
+
+        if self.function.ptr_eq(&as_closure.function) && same_upvalues {
+            Ok(Compare::Equal)
+        } else {
+            Ok(Compare::NotEqual)
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt::Display;
+
+use crate::{
+    errors::RuntimeErrorKind,
+    heap::{Heap, ObjRef, Tracer},
+    interner::Symbol,
+    object::Object,
+    value::Value,
+};
+
+pub const ERROR_TYPE: &str = "error";
+
+/// Runtime error caught by ``catch``, exposes ``kind``, ``message`` and ``line`` to scripts
+pub struct ErrorObject {
+    pub kind: ObjRef,    // string
+    pub message: ObjRef, // string
+    pub line: usize,
+}
+
+impl ErrorObject {
+    pub fn new(kind: &RuntimeErrorKind, line: usize, heap: &mut Heap) -> Self {
+        // every error kind is formatted as "NameError: message"
+        let text = kind.to_string();
+        let (name, message) = text.split_once(": ").unwrap_or(("Error", &text));
+        Self {
+            kind: heap.alloc_string(name),
+            message: heap.alloc_string(message),
+            line,
+        }
+    }
+}
+
+impl Display for ErrorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl std::fmt::Debug for ErrorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for ErrorObject {
+    fn type_name(&self) -> String {
+        String::from(ERROR_TYPE)
+    }
+
+    fn get_attribute(&self, attr_name: &Symbol) -> Option<Value> {
+        match attr_name.as_str() {
+            "kind" => Some(Value::Object(self.kind)),
+            "message" => Some(Value::Object(self.message)),
+            "line" => Some(Value::Int(self.line as i64)),
+            _ => None,
+        }
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark(self.kind);
+        tracer.mark(self.message);
+    }
+}
@@ -0,0 +1,91 @@
+use std::{any::Any, fmt::Display, rc::Rc};
+
+use crate::{
+    alias::StoredChunk,
+    cast,
+    chunk::Chunk,
+    errors::RuntimeErrorKind,
+    heap::{ObjRef, Tracer},
+    isinstance,
+    object::{Object, ResultRE},
+    rc_refcell,
+    token::Literal,
+    value::Compare,
+};
+
+pub const FUNCTION_TYPE: &str = "function";
+
+/// Where closure takes captured variable from when it is created
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpvalueDescriptor {
+    pub index: usize,
+    pub is_local: bool, // true - local of enclosing function, false - its upvalue
+}
+
+/// Compiled function, owns its own chunk of bytecode
+pub struct FunctionObject {
+    pub name: Option<Literal>,
+    pub arity: usize,
+    pub chunk: StoredChunk,
+    pub upvalues: Vec<UpvalueDescriptor>,
+}
+
+impl FunctionObject {
+    pub fn new(name: Option<Literal>) -> Self {
+        Self {
+            name,
+            arity: 0,
+            chunk: rc_refcell!(Chunk::new()),
+            upvalues: vec![],
+        }
+    }
+
+    pub fn with_chunk(name: Option<Literal>, chunk: StoredChunk) -> Self {
+        Self {
+            name,
+            arity: 0,
+            chunk,
+            upvalues: vec![],
+        }
+    }
+}
+
+impl Display for FunctionObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<script>"),
+        }
+    }
+}
+
+impl std::fmt::Debug for FunctionObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for FunctionObject {
+    fn type_name(&self) -> String {
+        String::from(FUNCTION_TYPE)
+    }
+
+    /// Constants of the chunk hold nested functions and string literals
+    fn trace(&self, tracer: &mut Tracer) {
+        for constant in &self.chunk.borrow().constants {
+            tracer.mark_value(constant);
+        }
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, FunctionObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_function = cast!(other => FunctionObject)?;
+        if Rc::ptr_eq(&as_function.chunk, &self.chunk) {
+            Ok(Compare::Equal)
+        } else {
+            Ok(Compare::NotEqual)
+        }
+    }
+}
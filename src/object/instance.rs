@@ -0,0 +1,63 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    heap::{ObjRef, Tracer},
+    object::{Object, ResultRE, class::ClassObject},
+    interner::Symbol,
+    value::Value,
+};
+
+/// Instance of user defined class
+pub struct InstanceObject {
+    pub class: ObjRef,
+    pub fields: RefCell<HashMap<Symbol, Value>>,
+}
+
+impl InstanceObject {
+    pub fn new(class: ObjRef) -> Self {
+        Self {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn class(&self) -> &ClassObject {
+        cast!(self.class => ClassObject).expect("Instance of non-class object")
+    }
+}
+
+impl Display for InstanceObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{} instance>", self.class().name)
+    }
+}
+
+impl std::fmt::Debug for InstanceObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for InstanceObject {
+    fn type_name(&self) -> String {
+        self.class().name.to_string()
+    }
+
+    fn get_attribute(&self, attr_name: &Symbol) -> Option<Value> {
+        self.fields.borrow().get(attr_name).cloned()
+    }
+
+    fn set_attribute(&self, attr_name: &Symbol, value: Value) -> ResultRE<()> {
+        self.fields.borrow_mut().insert(attr_name.clone(), value);
+        Ok(())
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark(self.class);
+        for value in self.fields.borrow().values() {
+            tracer.mark_value(value);
+        }
+    }
+}
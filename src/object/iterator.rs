@@ -0,0 +1,61 @@
+use std::{cell::RefCell, fmt::Display};
+
+use crate::{
+    heap::{Heap, ObjRef, Tracer},
+    object::{Object, ResultRE},
+    value::Value,
+};
+
+pub const ITERATOR_TYPE: &str = "iterator";
+
+type Advance = Box<dyn FnMut(&mut Heap) -> Option<Value>>;
+
+/// Iteration state over any host sequence, ``advance`` produces items one by one
+pub struct IteratorObject {
+    advance: RefCell<Advance>,
+    sources: Vec<Value>, // values read by ``advance``, kept alive as long as the iterator is
+}
+
+impl IteratorObject {
+    pub fn new(
+        sources: Vec<Value>,
+        advance: impl FnMut(&mut Heap) -> Option<Value> + 'static,
+    ) -> Self {
+        Self {
+            advance: RefCell::new(Box::new(advance)),
+            sources,
+        }
+    }
+}
+
+impl Display for IteratorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<iterator>")
+    }
+}
+
+impl std::fmt::Debug for IteratorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for IteratorObject {
+    fn type_name(&self) -> String {
+        String::from(ITERATOR_TYPE)
+    }
+
+    fn iterate(&self, this: ObjRef, _heap: &mut Heap) -> ResultRE<ObjRef> {
+        Ok(this)
+    }
+
+    fn next_item(&self, heap: &mut Heap) -> ResultRE<Option<Value>> {
+        Ok((self.advance.borrow_mut())(heap))
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        for source in &self.sources {
+            tracer.mark_value(source);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{any::Any, cell::RefCell, fmt::Display};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    heap::{Heap, ObjRef, Tracer},
+    isinstance,
+    object::{
+        Object, ResultRE,
+        iterator::IteratorObject,
+        recursion::{compare_guarded, display_guarded},
+    },
+    value::{Compare, Value},
+};
+
+pub const LIST_TYPE: &str = "list";
+
+/// Mutable sequence of values
+pub struct ListObject {
+    pub items: RefCell<Vec<Value>>,
+}
+
+impl ListObject {
+    pub fn new(items: Vec<Value>) -> Self {
+        Self {
+            items: RefCell::new(items),
+        }
+    }
+
+    /// Converts script index into vector position, negative index counts from the end
+    fn position(&self, index: &Value) -> ResultRE<usize> {
+        let Some(index) = index.as_int() else {
+            return Err(RuntimeErrorKind::TypeError {
+                expected: "integer index".to_owned(),
+                provided: match index {
+                    Value::Float(_) => format!("float {index}"),
+                    _ => index.type_name(),
+                },
+            });
+        };
+
+        let length = self.items.borrow().len() as i64;
+        let position = if index < 0 { length + index } else { index };
+        if position < 0 || position >= length {
+            return Err(RuntimeErrorKind::IndexOutOfRange {
+                index: index.to_string(),
+                length: length as usize,
+            });
+        }
+        Ok(position as usize)
+    }
+}
+
+impl Display for ListObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display_guarded(self, f, "[...]", |f| {
+            write!(f, "[")?;
+            for (i, item) in self.items.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{item}")?;
+            }
+            write!(f, "]")
+        })
+    }
+}
+
+impl std::fmt::Debug for ListObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for ListObject {
+    fn type_name(&self) -> String {
+        String::from(LIST_TYPE)
+    }
+
+    fn get_item(&self, index: &Value) -> ResultRE<Value> {
+        let position = self.position(index)?;
+        Ok(self.items.borrow()[position].clone())
+    }
+
+    fn set_item(&self, index: &Value, value: Value) -> ResultRE<()> {
+        let position = self.position(index)?;
+        self.items.borrow_mut()[position] = value;
+        Ok(())
+    }
+
+    fn len(&self) -> ResultRE<usize> {
+        Ok(self.items.borrow().len())
+    }
+
+    /// Items are read by index, so appending during iteration is visible to the loop
+    fn iterate(&self, this: ObjRef, heap: &mut Heap) -> ResultRE<ObjRef> {
+        let mut position = 0;
+        let iterator = IteratorObject::new(vec![Value::Object(this)], move |_heap| {
+            let list = cast!(this => ListObject).ok()?;
+            let item = list.items.borrow().get(position).cloned();
+            position += 1;
+            item
+        });
+        Ok(heap.alloc(iterator))
+    }
+
+    fn contains(&self, item: &Value) -> ResultRE<bool> {
+        for value in self.items.borrow().iter() {
+            if value.cmp(item)? == Compare::Equal {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        for item in self.items.borrow().iter() {
+            tracer.mark_value(item);
+        }
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, ListObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_list = cast!(other => ListObject)?;
+
+        let items = self.items.borrow();
+        let other_items = as_list.items.borrow();
+        if items.len() != other_items.len() {
+            return Ok(Compare::NotEqual);
+        }
+        compare_guarded(|| {
+            for (a, b) in items.iter().zip(other_items.iter()) {
+                if a.cmp(b)? != Compare::Equal {
+                    return Ok(Compare::NotEqual);
+                }
+            }
+            Ok(Compare::Equal)
+        })
+    }
+}
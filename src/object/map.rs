@@ -0,0 +1,170 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt::Display};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    heap::{Heap, ObjRef, Tracer},
+    isinstance,
+    object::{
+        Object, ResultRE,
+        iterator::IteratorObject,
+        recursion::{compare_guarded, display_guarded},
+    },
+    value::{Compare, Value},
+};
+
+pub const MAP_TYPE: &str = "map";
+
+/// Hash table which keeps entries in insertion order
+#[derive(Default)]
+pub struct MapEntries {
+    entries: Vec<(Value, Value)>,
+    buckets: HashMap<u64, Vec<usize>>, // key hash -> positions in entries
+}
+
+impl MapEntries {
+    fn find(&self, key: &Value) -> ResultRE<Option<usize>> {
+        let Some(bucket) = self.buckets.get(&key.hash_code()?) else {
+            return Ok(None);
+        };
+        for position in bucket {
+            if self.entries[*position].0.cmp(key)? == Compare::Equal {
+                return Ok(Some(*position));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get(&self, key: &Value) -> ResultRE<Option<Value>> {
+        Ok(self
+            .find(key)?
+            .map(|position| self.entries[position].1.clone()))
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) -> ResultRE<()> {
+        if let Some(position) = self.find(&key)? {
+            self.entries[position].1 = value;
+            return Ok(());
+        }
+
+        let hash = key.hash_code()?;
+        self.buckets
+            .entry(hash)
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        for (key, value) in &self.entries {
+            tracer.mark_value(key);
+            tracer.mark_value(value);
+        }
+    }
+}
+
+/// Dictionary with hashable keys
+pub struct MapObject {
+    pub entries: RefCell<MapEntries>,
+}
+
+impl MapObject {
+    pub fn new() -> Self {
+        Self {
+            entries: RefCell::new(MapEntries::default()),
+        }
+    }
+}
+
+impl Display for MapObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display_guarded(self, f, "{...}", |f| {
+            write!(f, "{{")?;
+            for (i, (key, value)) in self.entries.borrow().entries.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{key}: {value}")?;
+            }
+            write!(f, "}}")
+        })
+    }
+}
+
+impl std::fmt::Debug for MapObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for MapObject {
+    fn type_name(&self) -> String {
+        String::from(MAP_TYPE)
+    }
+
+    fn get_item(&self, index: &Value) -> ResultRE<Value> {
+        let Some(value) = self.entries.borrow().get(index)? else {
+            return Err(RuntimeErrorKind::KeyNotFound {
+                key: index.to_string(),
+            });
+        };
+        Ok(value)
+    }
+
+    fn set_item(&self, index: &Value, value: Value) -> ResultRE<()> {
+        self.entries.borrow_mut().insert(index.clone(), value)
+    }
+
+    fn contains(&self, item: &Value) -> ResultRE<bool> {
+        Ok(self.entries.borrow().find(item)?.is_some())
+    }
+
+    fn len(&self) -> ResultRE<usize> {
+        Ok(self.entries.borrow().len())
+    }
+
+    /// Iterates over keys present when the loop started, in insertion order
+    fn iterate(&self, _this: ObjRef, heap: &mut Heap) -> ResultRE<ObjRef> {
+        let keys = self.entries.borrow().keys();
+        let mut remaining = keys.clone().into_iter();
+        let iterator = IteratorObject::new(keys, move |_heap| remaining.next());
+        Ok(heap.alloc(iterator))
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        self.entries.borrow().trace(tracer);
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, MapObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_map = cast!(other => MapObject)?;
+
+        let entries = self.entries.borrow();
+        let other_entries = as_map.entries.borrow();
+        if entries.len() != other_entries.len() {
+            return Ok(Compare::NotEqual);
+        }
+        compare_guarded(|| {
+            for (key, value) in entries.entries.iter() {
+                let Some(other_value) = other_entries.get(key)? else {
+                    return Ok(Compare::NotEqual);
+                };
+                if value.cmp(&other_value)? != Compare::Equal {
+                    return Ok(Compare::NotEqual);
+                }
+            }
+            Ok(Compare::Equal)
+        })
+    }
+}
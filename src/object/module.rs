@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use crate::{
+    alias::StoredNameSpace,
+    heap::Tracer,
+    interner::Symbol,
+    object::Object,
+    token::Literal,
+    value::Value,
+};
+
+pub const MODULE_TYPE: &str = "module";
+
+/// Executed file, only its exported globals are accessible as attributes
+pub struct ModuleObject {
+    pub name: Literal,
+    pub globals: StoredNameSpace,
+}
+
+impl ModuleObject {
+    pub fn new(name: Literal, globals: StoredNameSpace) -> Self {
+        Self { name, globals }
+    }
+}
+
+impl Display for ModuleObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+impl std::fmt::Debug for ModuleObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for ModuleObject {
+    fn type_name(&self) -> String {
+        String::from(MODULE_TYPE)
+    }
+
+    fn get_attribute(&self, attr_name: &Symbol) -> Option<Value> {
+        self.globals.borrow().get_exported(attr_name)
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        self.globals.borrow().trace(tracer);
+    }
+}
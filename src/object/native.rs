@@ -0,0 +1,63 @@
+use std::{any::Any, fmt::Display, ops::RangeInclusive};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    heap::ObjRef,
+    isinstance,
+    object::{Object, ResultRE},
+    interner::Symbol,
+    value::{Compare, Value},
+    vm::VirtualMachine,
+};
+
+pub const NATIVE_FUNCTION_TYPE: &str = "native function";
+
+pub type NativeFn = fn(&mut VirtualMachine, &[Value]) -> ResultRE<Value>;
+
+/// Function implemented on the host side
+pub struct NativeFunctionObject {
+    pub name: Symbol,
+    pub arity: RangeInclusive<usize>, // natives may have optional trailing arguments
+    pub callback: NativeFn,
+}
+
+impl NativeFunctionObject {
+    pub fn new(name: Symbol, arity: RangeInclusive<usize>, callback: NativeFn) -> Self {
+        Self {
+            name,
+            arity,
+            callback,
+        }
+    }
+}
+
+impl Display for NativeFunctionObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl std::fmt::Debug for NativeFunctionObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for NativeFunctionObject {
+    fn type_name(&self) -> String {
+        String::from(NATIVE_FUNCTION_TYPE)
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, NativeFunctionObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_native = cast!(other => NativeFunctionObject)?;
+        if std::ptr::fn_addr_eq(as_native.callback, self.callback) && as_native.name == self.name {
+            Ok(Compare::Equal)
+        } else {
+            Ok(Compare::NotEqual)
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{any::Any, fmt::Display, iter};
+
+use crate::{
+    cast,
+    errors::RuntimeErrorKind,
+    heap::{Heap, ObjRef},
+    isinstance,
+    object::{Object, ResultRE, iterator::IteratorObject},
+    value::{Compare, Value},
+};
+
+pub const RANGE_TYPE: &str = "range";
+
+/// Lazy sequence of integers from ``start`` up to, but not including, ``stop``
+#[derive(Clone)]
+pub struct RangeObject {
+    pub start: i64,
+    pub stop: i64,
+    pub step: i64, // never zero, negative step counts down
+}
+
+impl RangeObject {
+    pub fn new(start: i64, stop: i64, step: i64) -> ResultRE<Self> {
+        if step == 0 {
+            return Err(RuntimeErrorKind::InvalidValue {
+                message: "range step cannot be zero".to_owned(),
+            });
+        }
+        Ok(Self { start, stop, step })
+    }
+
+    fn is_before_stop(&self, value: i64) -> bool {
+        if self.step > 0 {
+            value < self.stop
+        } else {
+            value > self.stop
+        }
+    }
+}
+
+impl Display for RangeObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "range({}, {}, {})", self.start, self.stop, self.step)
+    }
+}
+
+impl std::fmt::Debug for RangeObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for RangeObject {
+    fn type_name(&self) -> String {
+        String::from(RANGE_TYPE)
+    }
+
+    fn iterate(&self, _this: ObjRef, heap: &mut Heap) -> ResultRE<ObjRef> {
+        let (range, step) = (self.clone(), self.step);
+        let mut values = iter::successors(Some(self.start), move |value| value.checked_add(step))
+            .take_while(move |value| range.is_before_stop(*value))
+            .map(Value::Int);
+        let iterator = IteratorObject::new(vec![], move |_heap| values.next());
+        Ok(heap.alloc(iterator))
+    }
+
+    fn contains(&self, item: &Value) -> ResultRE<bool> {
+        let Some(value) = item.as_int() else {
+            return Ok(false);
+        };
+        let is_started = if self.step > 0 {
+            value >= self.start
+        } else {
+            value <= self.start
+        };
+        let is_on_step = (value as i128 - self.start as i128) % self.step as i128 == 0;
+        Ok(is_started && self.is_before_stop(value) && is_on_step)
+    }
+
+    fn len(&self) -> ResultRE<usize> {
+        if !self.is_before_stop(self.start) {
+            return Ok(0);
+        }
+        let distance = (self.stop as i128 - self.start as i128).abs();
+        let step = (self.step as i128).abs();
+        Ok(((distance + step - 1) / step) as usize)
+    }
+
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
+        if !isinstance!(other, RangeObject) {
+            return Ok(Compare::NotEqual);
+        }
+        let as_range = cast!(other => RangeObject)?;
+        if (as_range.start, as_range.stop, as_range.step) == (self.start, self.stop, self.step) {
+            Ok(Compare::Equal)
+        } else {
+            Ok(Compare::NotEqual)
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt::{Formatter, Result},
+};
+
+use crate::{errors::RuntimeErrorKind, object::ResultRE};
+
+/// Deepest nesting of containers compared by ``==``, beyond it the containers are assumed cyclic
+pub const MAX_COMPARE_DEPTH: usize = 512;
+
+thread_local! {
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(vec![]) };
+    static COMPARE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Writes container with ``write``, or ``placeholder`` if it is already being written,
+/// so self-referential containers are printed as ``[...]`` instead of recursing forever
+pub fn display_guarded<T>(
+    container: &T,
+    f: &mut Formatter<'_>,
+    placeholder: &str,
+    write: impl FnOnce(&mut Formatter<'_>) -> Result,
+) -> Result {
+    let ptr = (container as *const T).cast::<()>();
+    if PRINTING.with_borrow(|printing| printing.contains(&ptr)) {
+        return write!(f, "{placeholder}");
+    }
+    PRINTING.with_borrow_mut(|printing| printing.push(ptr));
+    let result = write(f);
+    PRINTING.with_borrow_mut(|printing| printing.pop());
+    result
+}
+
+/// Runs one level of structural comparison, erroring out once containers nest too deep
+pub fn compare_guarded<T>(compare: impl FnOnce() -> ResultRE<T>) -> ResultRE<T> {
+    let depth = COMPARE_DEPTH.get();
+    if depth >= MAX_COMPARE_DEPTH {
+        return Err(RuntimeErrorKind::ComparisonDepth {
+            max_depth: MAX_COMPARE_DEPTH,
+        });
+    }
+    COMPARE_DEPTH.set(depth + 1);
+    let result = compare();
+    COMPARE_DEPTH.set(depth);
+    result
+}
@@ -1,18 +1,23 @@
-use std::{any::Any, fmt::Display, rc::Rc};
+use std::{
+    any::Any,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+};
 
 use crate::{
-    alias::{DynObject, StoredValue}, cast, errors::RuntimeErrorKind, isinstance, object::{Object, ResultRE}, rc_refcell, token::Literal, value::{Compare, Value}
+    cast, errors::RuntimeErrorKind, heap::{Heap, ObjRef}, interner::Symbol, isinstance, object::{Object, ResultRE, iterator::IteratorObject}, value::{Compare, Value}
 };
 
 pub const STRING_TYPE: &str = "string";
 
+/// Strings are always interned, so equal strings share the same symbol
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub struct StringObject {
-    pub value: Literal,
+    pub value: Symbol,
 }
 
 impl StringObject {
-    pub fn new(value: Literal) -> Self {
+    pub fn new(value: Symbol) -> Self {
         Self { value }
     }
 }
@@ -28,11 +33,7 @@ impl Object for StringObject {
         String::from(STRING_TYPE)
     }
 
-    fn copy(&self) -> DynObject {
-        Box::new(StringObject::new(self.value.clone()))
-    }
-
-    fn cmp(&self, other: &DynObject) -> ResultRE<Compare> {
+    fn cmp(&self, other: ObjRef) -> ResultRE<Compare> {
         if !isinstance!(other, StringObject) {
             return Ok(Compare::NotEqual);
         }
@@ -44,7 +45,43 @@ impl Object for StringObject {
         }
     }
 
-    fn add(&self, other: &DynObject) -> ResultRE<StoredValue> {
+    fn len(&self) -> ResultRE<usize> {
+        Ok(self.value.chars().count())
+    }
+
+    /// Iterates by character, each one as a single character string
+    fn iterate(&self, _this: ObjRef, heap: &mut Heap) -> ResultRE<ObjRef> {
+        let mut chars = self.value.chars().collect::<Vec<char>>().into_iter();
+        let iterator = IteratorObject::new(vec![], move |heap| {
+            let char = chars.next()?;
+            let string = heap.alloc_string(char.encode_utf8(&mut [0; 4]));
+            Some(Value::Object(string))
+        });
+        Ok(heap.alloc(iterator))
+    }
+
+    fn contains(&self, item: &Value) -> ResultRE<bool> {
+        let not_string = || RuntimeErrorKind::TypeError {
+            expected: STRING_TYPE.to_owned(),
+            provided: item.type_name(),
+        };
+        let Value::Object(obj) = item else {
+            return Err(not_string());
+        };
+        if !isinstance!(obj, StringObject) {
+            return Err(not_string());
+        }
+        let as_string = cast!(obj => StringObject)?;
+        Ok(self.value.contains(as_string.value.as_str()))
+    }
+
+    fn hash_code(&self) -> ResultRE<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.value.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    fn add(&self, other: ObjRef, heap: &mut Heap) -> ResultRE<Value> {
         if !isinstance!(other, StringObject) {
             return Err(self.operation_not_supported(other, "+".to_owned()));
         }
@@ -53,8 +90,7 @@ impl Object for StringObject {
         concatenated_string.push_str(&self.value);
         concatenated_string.push_str(&as_string.value);
 
-        Ok(rc_refcell!(Value::Object(Box::new(StringObject::new(
-            Rc::new(concatenated_string)
-        )))))
+        let concatenated = heap.alloc_string(&concatenated_string);
+        Ok(Value::Object(concatenated))
     }
 }
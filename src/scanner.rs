@@ -7,6 +7,8 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize, // index of the first char of current line
+    interpolations: Vec<usize>, // unclosed braces inside each embedded expression being scanned
     keywords: HashMap<String, TokenType>,
 }
 
@@ -17,21 +19,35 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            interpolations: vec![],
             keywords: HashMap::from([
                 (String::from("and"), TokenType::AND),
+                (String::from("as"), TokenType::AS),
+                (String::from("break"), TokenType::BREAK),
+                (String::from("catch"), TokenType::CATCH),
                 (String::from("class"), TokenType::CLASS),
+                (String::from("const"), TokenType::CONST),
+                (String::from("continue"), TokenType::CONTINUE),
                 (String::from("else"), TokenType::ELSE),
+                (String::from("export"), TokenType::EXPORT),
                 (String::from("false"), TokenType::FALSE),
+                (String::from("finally"), TokenType::FINALLY),
                 (String::from("for"), TokenType::FOR),
                 (String::from("fn"), TokenType::FUN),
+                (String::from("from"), TokenType::FROM),
                 (String::from("if"), TokenType::IF),
+                (String::from("import"), TokenType::IMPORT),
+                (String::from("in"), TokenType::IN),
                 (String::from("null"), TokenType::NIL),
                 (String::from("or"), TokenType::OR),
                 (String::from("print"), TokenType::PRINT),
                 (String::from("return"), TokenType::RETURN),
                 (String::from("super"), TokenType::SUPER),
                 (String::from("this"), TokenType::THIS),
+                (String::from("throw"), TokenType::THROW),
                 (String::from("true"), TokenType::TRUE),
+                (String::from("try"), TokenType::TRY),
                 (String::from("var"), TokenType::VAR),
                 (String::from("while"), TokenType::WHILE),
             ]),
@@ -50,14 +66,58 @@ impl Scanner {
         match c {
             '(' => self.make_token(TokenType::LeftParen),
             ')' => self.make_token(TokenType::RightParen),
-            '{' => self.make_token(TokenType::LeftBrace),
-            '}' => self.make_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(braces) = self.interpolations.last_mut() {
+                    *braces += 1;
+                }
+                self.make_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // end of embedded expression, scanning the rest of the string
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string()
+                }
+                Some(braces) => {
+                    *braces -= 1;
+                    self.make_token(TokenType::RightBrace)
+                }
+                None => self.make_token(TokenType::RightBrace),
+            },
+            '[' => self.make_token(TokenType::LeftBracket),
+            ']' => self.make_token(TokenType::RightBracket),
             ';' => self.make_token(TokenType::SEMICOLON),
+            ':' => self.make_token(TokenType::COLON),
+            '?' => self.make_token(TokenType::QUESTION),
             ',' => self.make_token(TokenType::COMMA),
             '.' => self.make_token(TokenType::DOT),
-            '-' => self.make_token(TokenType::MINUS),
-            '+' => self.make_token(TokenType::PLUS),
-            '*' => self.make_token(TokenType::STAR),
+            '-' => {
+                let is_equal = self.matches('=');
+                self.make_token(if is_equal {
+                    TokenType::MinusEqual
+                } else {
+                    TokenType::MINUS
+                })
+            }
+            '+' => {
+                let is_equal = self.matches('=');
+                self.make_token(if is_equal {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::PLUS
+                })
+            }
+            '*' => {
+                if self.matches('*') {
+                    return self.make_token(TokenType::StarStar);
+                }
+                let is_equal = self.matches('=');
+                self.make_token(if is_equal {
+                    TokenType::StarEqual
+                } else {
+                    TokenType::STAR
+                })
+            }
             '!' => {
                 let is_equal = self.matches('=');
                 self.make_token(if is_equal {
@@ -75,6 +135,9 @@ impl Scanner {
                 })
             }
             '<' => {
+                if self.matches('<') {
+                    return self.make_token(TokenType::LessLess);
+                }
                 let is_equal = self.matches('=');
                 self.make_token(if is_equal {
                     TokenType::LessEqual
@@ -83,6 +146,9 @@ impl Scanner {
                 })
             }
             '>' => {
+                if self.matches('>') {
+                    return self.make_token(TokenType::GreaterGreater);
+                }
                 let is_equal = self.matches('=');
                 self.make_token(if is_equal {
                     TokenType::GreaterEqual
@@ -90,20 +156,26 @@ impl Scanner {
                     TokenType::GREATER
                 })
             }
+            '&' => self.make_token(TokenType::AMPERSAND),
+            '|' => self.make_token(TokenType::PIPE),
+            '^' => self.make_token(TokenType::CARET),
+            '~' => {
+                let is_slash = self.matches('/');
+                self.make_token(if is_slash {
+                    TokenType::TildeSlash
+                } else {
+                    TokenType::TILDE
+                })
+            }
             '/' => {
-                let is_equal_slash = self.matches('/');
-                let is_equal_equal = self.matches('=');
-
-                if is_equal_equal {
-                    return self.make_token(TokenType::SlashEqual);
-                }
-
-                if !is_equal_slash {
-                    self.make_token(TokenType::SLASH)
+                let is_equal = self.matches('=');
+                self.make_token(if is_equal {
+                    TokenType::SlashEqual
                 } else {
-                    panic!("Comment not skipped!");
-                }
+                    TokenType::SLASH
+                })
             }
+            '%' => self.make_token(TokenType::PERCENT),
             '"' => self.string(),
             val if self.is_digit(val) => self.number(),
             val if self.is_alpha(val) => self.identifier(),
@@ -112,13 +184,22 @@ impl Scanner {
     }
 
     fn make_token(&self, token_type: TokenType) -> Token {
-        Token::new(token_type, self.line, self.start, self.length(), None, None)
+        Token::new(
+            token_type,
+            self.line,
+            self.column(self.start),
+            self.start,
+            self.length(),
+            None,
+            None,
+        )
     }
 
     fn make_literal_token(&self, token_type: TokenType, literal: String) -> Token {
         Token::new(
             token_type,
             self.line,
+            self.column(self.start),
             self.start,
             self.length(),
             Some(Rc::new(literal)),
@@ -127,16 +208,25 @@ impl Scanner {
     }
 
     fn make_error_token(&self, message: String) -> Token {
+        self.make_error_token_at(message, self.start, self.length())
+    }
+
+    fn make_error_token_at(&self, message: String, start: usize, length: usize) -> Token {
         Token::new(
             TokenType::Error,
             self.line,
-            self.start,
-            self.length(),
+            self.column(start),
+            start,
+            length,
             None,
             Some(message),
         )
     }
 
+    fn column(&self, index: usize) -> usize {
+        index.saturating_sub(self.line_start) + 1
+    }
+
     fn char_at(&self, index: usize) -> char {
         self.source
             .chars()
@@ -204,14 +294,11 @@ impl Scanner {
                 '\n' => {
                     self.line += 1;
                     self.advance();
+                    self.line_start = self.current;
                 }
-                '/' => {
-                    if self.peek_next() == '/' {
-                        while self.peek() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
-                    } else {
-                        return;
+                '/' if self.peek_next() == '/' => {
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
                     }
                 }
                 _ => return,
@@ -220,26 +307,107 @@ impl Scanner {
     }
 
     fn string(&mut self) -> Token {
-        loop {
-            let peek = self.peek();
+        let mut literal = String::new();
+        let mut error: Option<Token> = None;
 
-            if peek == '"' || self.is_at_end() {
-                break;
+        loop {
+            if self.is_at_end() {
+                return self.make_error_token("Unclosed string literal".to_owned());
             }
 
-            if peek == '\n' {
-                self.line += 1;
+            let c = self.advance();
+            match c {
+                '"' => break,
+                '$' if self.peek() == '{' => {
+                    self.advance();
+                    if let Some(error) = error {
+                        return error;
+                    }
+                    self.interpolations.push(0);
+                    return self.make_literal_token(TokenType::INTERPOLATION, literal);
+                }
+                '\n' => {
+                    self.line += 1;
+                    self.line_start = self.current;
+                    literal.push(c);
+                }
+                '\\' => match self.escape() {
+                    Ok(escaped) => literal.push(escaped),
+                    // rest of the string is still consumed, so scanning can continue after it
+                    Err(token) => error = error.or(Some(token)),
+                },
+                _ => literal.push(c),
             }
+        }
 
-            self.advance();
+        if let Some(error) = error {
+            return error;
         }
+        self.make_literal_token(TokenType::STRING, literal)
+    }
 
+    /// Decodes escape sequence after backslash, returns error token pointing at the sequence
+    fn escape(&mut self) -> Result<char, Token> {
+        let escape_start = self.current - 1;
         if self.is_at_end() {
-            return self.make_error_token("Unclosed string literal".to_owned());
+            return Err(self.make_error_token_at(
+                "Unclosed string literal".to_owned(),
+                escape_start,
+                1,
+            ));
         }
-        self.advance();
-        let literal = self.substr(self.start + 1, self.current - 1);
-        self.make_literal_token(TokenType::STRING, literal)
+
+        let c = self.advance();
+        let escaped = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => return self.unicode_escape(escape_start),
+            _ => {
+                return Err(self.make_error_token_at(
+                    format!("Invalid escape sequence '\\{c}'"),
+                    escape_start,
+                    2,
+                ));
+            }
+        };
+        Ok(escaped)
+    }
+
+    /// Decodes ``\u{XXXX}`` escape with 1 to 6 hex digits
+    fn unicode_escape(&mut self, escape_start: usize) -> Result<char, Token> {
+        let invalid = |scanner: &Self| {
+            scanner.make_error_token_at(
+                "Invalid unicode escape, expected '\\u{XXXX}' with 1 to 6 hex digits".to_owned(),
+                escape_start,
+                scanner.current - escape_start,
+            )
+        };
+
+        if !self.matches('{') {
+            return Err(invalid(self));
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = self.substr(digits_start, self.current);
+        if digits.is_empty() || digits.len() > 6 || !self.matches('}') {
+            return Err(invalid(self));
+        }
+
+        let code = u32::from_str_radix(&digits, 16).expect("hex digits checked above");
+        char::from_u32(code).ok_or_else(|| {
+            self.make_error_token_at(
+                format!("Invalid unicode code point '{digits}'"),
+                escape_start,
+                self.current - escape_start,
+            )
+        })
     }
 
     fn is_alpha(&self, c: char) -> bool {
@@ -251,14 +419,16 @@ impl Scanner {
             self.advance();
         }
 
+        let mut token_type = TokenType::INTEGER;
         if self.peek() == '.' && self.is_digit(self.peek_next()) {
+            token_type = TokenType::NUMBER;
             self.advance();
             while self.is_digit(self.peek()) {
                 self.advance();
             }
         }
         let literal = self.substr(self.start, self.current);
-        self.make_literal_token(TokenType::NUMBER, literal)
+        self.make_literal_token(token_type, literal)
     }
 
     fn identifier(&mut self) -> Token {
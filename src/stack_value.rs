@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+
+use crate::{
+    heap::{Heap, Tracer},
+    value::Value,
+};
+
+#[cfg(feature = "nan-boxing")]
+pub mod nan_boxed;
+
+/// Representation of values on the VM stack, ``Value`` itself unless ``nan-boxing`` is enabled
+#[cfg(not(feature = "nan-boxing"))]
+pub type Slot = Value;
+#[cfg(feature = "nan-boxing")]
+pub type Slot = nan_boxed::NanBoxed;
+
+/// Conversion between values and their stack representation
+pub trait StackValue: Clone {
+    /// ``heap`` is used only for values which cannot be encoded inline
+    fn pack(value: Value, heap: &RefCell<Heap>) -> Self;
+
+    fn unpack(&self) -> Value;
+
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+impl StackValue for Value {
+    fn pack(value: Value, _heap: &RefCell<Heap>) -> Self {
+        value
+    }
+
+    fn unpack(&self) -> Value {
+        self.clone()
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark_value(self);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{cell::RefCell, fmt::Display};
+
+use crate::{
+    heap::{Heap, ObjRef, Tracer},
+    object::Object,
+    stack_value::StackValue,
+    value::Value,
+};
+
+#[cfg(not(target_pointer_width = "64"))]
+compile_error!("nan-boxing requires 64-bit pointers");
+
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const QNAN: u64 = 0x7ffc_0000_0000_0000; // quiet NaN, never produced by float arithmetic
+const INT_TAG: u64 = 0x0001_0000_0000_0000; // for objects marks boxed values
+const PAYLOAD_MASK: u64 = 0x0000_ffff_ffff_ffff; // 48 bits
+
+const NULL_TAG: u64 = 1;
+const FALSE_TAG: u64 = 2;
+const TRUE_TAG: u64 = 3;
+
+const INT_INLINE_MIN: i64 = -(1 << 47);
+const INT_INLINE_MAX: i64 = (1 << 47) - 1;
+
+/// Value encoded into 64 bits: floats are stored as is, everything else hides in the
+/// payload of a quiet NaN. Objects keep their 48-bit address under the sign bit, ints
+/// which do not fit into 48 bits and identifiers are boxed on the heap.
+/// Not ``Copy``, so the VM code stays the same for both stack representations
+#[derive(Clone)]
+pub struct NanBoxed(u64);
+
+impl NanBoxed {
+    fn float(value: f64) -> Self {
+        // any NaN payload could collide with tags, so NaNs are canonicalized
+        if value.is_nan() {
+            Self(f64::NAN.to_bits())
+        } else {
+            Self(value.to_bits())
+        }
+    }
+
+    fn object(obj: ObjRef) -> Self {
+        let bits = obj.to_bits();
+        debug_assert!(bits & !PAYLOAD_MASK == 0, "Object address does not fit into 48 bits");
+        Self(SIGN_BIT | QNAN | bits)
+    }
+
+    fn boxed(value: Value, heap: &RefCell<Heap>) -> Self {
+        let obj = heap.borrow_mut().alloc(BoxedValue(value));
+        Self(Self::object(obj).0 | INT_TAG)
+    }
+
+    fn is_float(&self) -> bool {
+        self.0 & QNAN != QNAN
+    }
+
+    fn is_object(&self) -> bool {
+        self.0 & (SIGN_BIT | QNAN) == SIGN_BIT | QNAN
+    }
+
+    fn is_boxed(&self) -> bool {
+        self.0 & (SIGN_BIT | QNAN | INT_TAG) == SIGN_BIT | QNAN | INT_TAG
+    }
+
+    fn is_int(&self) -> bool {
+        self.0 & (SIGN_BIT | QNAN | INT_TAG) == QNAN | INT_TAG
+    }
+
+    fn as_object(&self) -> ObjRef {
+        // SAFETY: object bits are only ever produced from a live handle in ``object()``
+        unsafe { ObjRef::from_bits(self.0 & PAYLOAD_MASK) }
+    }
+
+    fn as_int(&self) -> i64 {
+        // sign extension of the 48-bit payload
+        (((self.0 & PAYLOAD_MASK) << 16) as i64) >> 16
+    }
+}
+
+impl StackValue for NanBoxed {
+    fn pack(value: Value, heap: &RefCell<Heap>) -> Self {
+        match value {
+            Value::Float(value) => Self::float(value),
+            Value::Int(value) if (INT_INLINE_MIN..=INT_INLINE_MAX).contains(&value) => {
+                Self(QNAN | INT_TAG | (value as u64 & PAYLOAD_MASK))
+            }
+            Value::Boolean(true) => Self(QNAN | TRUE_TAG),
+            Value::Boolean(false) => Self(QNAN | FALSE_TAG),
+            Value::Null => Self(QNAN | NULL_TAG),
+            Value::Object(obj) => Self::object(obj),
+            value @ (Value::Int(_) | Value::Identifier(_)) => Self::boxed(value, heap),
+        }
+    }
+
+    fn unpack(&self) -> Value {
+        if self.is_float() {
+            return Value::Float(f64::from_bits(self.0));
+        }
+        if self.is_int() {
+            return Value::Int(self.as_int());
+        }
+        if self.is_boxed() {
+            let obj = self.as_object();
+            let boxed = (obj.as_ref() as &dyn std::any::Any).downcast_ref::<BoxedValue>();
+            return boxed.expect("Boxed tag on non-boxed object").0.clone();
+        }
+        if self.is_object() {
+            return Value::Object(self.as_object());
+        }
+        match self.0 & !QNAN {
+            NULL_TAG => Value::Null,
+            FALSE_TAG => Value::Boolean(false),
+            TRUE_TAG => Value::Boolean(true),
+            _ => unreachable!("Invalid NaN-boxed value {:#x}", self.0),
+        }
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        if self.is_object() {
+            tracer.mark(self.as_object());
+        }
+    }
+}
+
+/// Value without inline encoding, never visible to scripts, ``unpack`` returns the inner value
+struct BoxedValue(Value);
+
+impl Display for BoxedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::fmt::Debug for BoxedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Object for BoxedValue {
+    fn type_name(&self) -> String {
+        self.0.type_name()
+    }
+}
@@ -9,13 +9,20 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     COMMA,
     DOT,
     MINUS,
     PLUS,
     SEMICOLON,
+    COLON,
+    QUESTION,
     SLASH,
     STAR,
+    PERCENT,
+    StarStar,
+    TildeSlash,
     BANG,
     BangEqual,
     EQUAL,
@@ -25,23 +32,46 @@ pub enum TokenType {
     LESS,
     LessEqual,
     SlashEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    LessLess,
+    GreaterGreater,
     IDENTIFIER,
     STRING,
+    INTERPOLATION, // string segment followed by embedded expression
     NUMBER,
+    INTEGER,
     AND,
+    AS,
+    BREAK,
+    CATCH,
     CLASS,
+    CONST,
+    CONTINUE,
     ELSE,
+    EXPORT,
     FALSE,
+    FINALLY,
     FUN,
     FOR,
+    FROM,
     IF,
+    IMPORT,
+    IN,
     NIL,
     OR,
     PRINT,
     RETURN,
     SUPER,
     THIS,
+    THROW,
     TRUE,
+    TRY,
     VAR,
     WHILE,
     EOF,
@@ -52,6 +82,7 @@ pub enum TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub line: usize,
+    pub column: usize,
     pub start: usize,
     pub length: usize,
     pub literal: Option<Literal>,
@@ -62,6 +93,7 @@ impl Token {
     pub fn new(
         token_type: TokenType,
         line: usize,
+        column: usize,
         start: usize,
         length: usize,
         literal: Option<Literal>,
@@ -82,6 +114,7 @@ impl Token {
         Self {
             token_type,
             line,
+            column,
             start,
             length,
             message,
@@ -1,14 +1,19 @@
-use std::fmt::Display;
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+};
 
-use crate::{alias::DynObject, object::ResultRE, token::Literal};
+use crate::{errors::RuntimeErrorKind, heap::ObjRef, object::ResultRE, interner::Symbol};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
+    Int(i64),
     Float(f64),
     Boolean(bool),
     Null,
-    Identifier(Literal),
-    Object(DynObject),
+    Identifier(Symbol),
+    Object(ObjRef),
 }
 
 #[derive(PartialEq, Debug)]
@@ -19,9 +24,50 @@ pub enum Compare {
     Lower,
 }
 
+impl Compare {
+    /// Result of the comparison with swapped operands
+    pub fn reversed(self) -> Self {
+        match self {
+            Compare::Greater => Compare::Lower,
+            Compare::Lower => Compare::Greater,
+            other => other,
+        }
+    }
+}
+
+impl From<Option<Ordering>> for Compare {
+    fn from(ordering: Option<Ordering>) -> Self {
+        match ordering {
+            Some(Ordering::Greater) => Compare::Greater,
+            Some(Ordering::Less) => Compare::Lower,
+            Some(Ordering::Equal) => Compare::Equal,
+            None => Compare::NotEqual, // NaN is not ordered with anything
+        }
+    }
+}
+
+/// Int equals float only if the float converts to exactly that int,
+/// the same rule ``hash_code`` uses, so equal numbers always share a hash
+fn cmp_int_float(int: i64, float: f64) -> Compare {
+    if let Some(exact) = Value::Float(float).as_int() {
+        return Some(int.cmp(&exact)).into();
+    }
+    if float.is_nan() || float.fract() != 0.0 {
+        // rounding of a big int never crosses a fractional float, those are below 2^52
+        return (int as f64).partial_cmp(&float).into();
+    }
+    // integral float beyond the range of ints
+    if float > 0.0 {
+        Compare::Lower
+    } else {
+        Compare::Greater
+    }
+}
+
 impl Value {
     pub fn type_name(&self) -> String {
         match self {
+            Value::Int(_) => "int".to_owned(),
             Value::Float(_) => "float".to_owned(),
             Value::Boolean(_) => "boolean".to_owned(),
             Value::Null => "null".to_owned(),
@@ -31,16 +77,66 @@ impl Value {
     }
 
     pub fn support_negation(&self) -> bool {
-        matches!(self, Value::Float(_))
+        matches!(self, Value::Int(_) | Value::Float(_))
+    }
+
+    /// Numeric value as float, ints are promoted
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Numeric value as int, floats are accepted only without fractional part
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            Value::Float(value)
+                if value.fract() == 0.0 && *value >= i64::MIN as f64 && *value < i64::MAX as f64 =>
+            {
+                Some(*value as i64)
+            }
+            _ => None,
+        }
     }
 
     pub fn as_bool(&self) -> bool {
         !matches!(self, Value::Boolean(false) | Value::Null)
     }
 
+    /// Hash consistent with equality, used for map keys
+    pub fn hash_code(&self) -> ResultRE<u64> {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            // NaN is not equal to itself, so the key could never be found again
+            Value::Float(value) if value.is_nan() => {
+                return Err(RuntimeErrorKind::InvalidValue {
+                    message: "NaN cannot be used as a map key".to_owned(),
+                });
+            }
+            // 1 == 1.0 and 0.0 == -0.0, so floats converting exactly to ints are hashed as ints
+            Value::Int(_) | Value::Float(_) if self.as_int().is_some() => {
+                self.as_int().hash(&mut hasher)
+            }
+            Value::Int(value) => value.hash(&mut hasher),
+            Value::Float(value) => value.to_bits().hash(&mut hasher),
+            Value::Boolean(value) => value.hash(&mut hasher),
+            Value::Null => 0u8.hash(&mut hasher),
+            Value::Object(obj) => return obj.hash_code(),
+            Value::Identifier(_) => {
+                return Err(RuntimeErrorKind::Unhashable {
+                    type_name: self.type_name(),
+                });
+            }
+        };
+        Ok(hasher.finish())
+    }
+
     pub fn cmp(&self, other: &Value) -> ResultRE<Compare> {
         match (&self, other) {
-            (Value::Float(a), Value::Float(b)) => {
+            (Value::Int(a), Value::Int(b)) => {
                 if a > b {
                     Ok(Compare::Greater)
                 } else if a < b {
@@ -49,6 +145,9 @@ impl Value {
                     Ok(Compare::Equal)
                 }
             }
+            (Value::Float(a), Value::Float(b)) => Ok(a.partial_cmp(b).into()),
+            (Value::Int(a), Value::Float(b)) => Ok(cmp_int_float(*a, *b)),
+            (Value::Float(a), Value::Int(b)) => Ok(cmp_int_float(*b, *a).reversed()),
             (Value::Boolean(a), Value::Boolean(b)) => {
                 if a == b {
                     Ok(Compare::Equal)
@@ -57,7 +156,8 @@ impl Value {
                 }
             }
             (Value::Null, Value::Null) => Ok(Compare::Equal),
-            (Value::Object(a), Value::Object(b)) => a.cmp(b),
+            (Value::Object(a), Value::Object(b)) if a.ptr_eq(b) => Ok(Compare::Equal),
+            (Value::Object(a), Value::Object(b)) => a.cmp(*b),
             _ => Ok(Compare::NotEqual),
         }
     }
@@ -66,6 +166,9 @@ impl Value {
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let repr = match self {
+            Value::Int(value) => format!("{value}"),
+            // keeping the fractional part, so floats are distinguishable from ints
+            Value::Float(value) if value.fract() == 0.0 => format!("{value:.1}"),
             Value::Float(value) => format!("{value}"),
             Value::Boolean(value) => format!("{value}"),
             Value::Null => "null".to_owned(),
@@ -76,31 +179,17 @@ impl Display for Value {
     }
 }
 
-impl Clone for Value {
-    fn clone(&self) -> Self {
-        match self {
-            Value::Float(val) => Value::Float(*val),
-            Value::Boolean(val) => Value::Boolean(*val),
-            Value::Null => Value::Null,
-            Value::Object(object) => Value::Object(object.copy()),
-            Value::Identifier(val) => Value::Identifier(val.clone()),
-        }
-    }
-}
-
-impl Drop for Value {
-    fn drop(&mut self) {
-    }
-}
-
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (&self, other) {
-            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(_) | Value::Int(_), Value::Float(_) | Value::Int(_)) => {
+                self.cmp(other).is_ok_and(|compare| compare == Compare::Equal)
+            }
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::Null, Value::Null) => true,
             (Value::Object(a), Value::Object(b)) => {
-                a.cmp(b).unwrap_or(Compare::NotEqual) == Compare::Equal
+                a.ptr_eq(b) || a.cmp(*b).unwrap_or(Compare::NotEqual) == Compare::Equal
             }
             _ => false,
         }
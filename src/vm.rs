@@ -1,147 +1,569 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use anyhow::Error;
 
-use crate::alias::{StoredChunk, StoredValue, VoidResult};
-use crate::bin_op::BinOpKind;
-use crate::chunk::OpCodeKind;
+use crate::alias::{StoredChunk, StoredHeap, StoredNameSpace, StoredUpvalue, VoidResult};
+use crate::bin_op::{BinOpKind, integral_operand};
+use crate::chunk::{Chunk, OpCodeKind};
+use crate::compiler::Compiler;
 use crate::errors::RuntimeError;
 use crate::errors::RuntimeErrorKind;
+use crate::heap::{ObjRef, Tracer};
+use crate::interner::Symbol;
+use crate::modules;
 use crate::namespace::NameSpace;
-use crate::rc_refcell;
+use crate::object::bound_method::BoundMethodObject;
+use crate::object::class::ClassObject;
+use crate::object::error::ErrorObject;
+use crate::object::closure::{ClosureObject, Upvalue};
+use crate::object::instance::InstanceObject;
+use crate::object::list::ListObject;
+use crate::object::map::MapObject;
+use crate::object::module::ModuleObject;
+use crate::object::native::{NativeFn, NativeFunctionObject};
+use crate::object::function::FunctionObject;
+use crate::object::Object;
+use crate::stack_value::{Slot, StackValue};
 use crate::value::{Compare, Value};
+use crate::{cast, isinstance, rc_refcell};
 
-type ValueStack = Vec<StoredValue>;
+type ValueStack = Vec<Slot>;
 
-pub struct VirtualMachine<'ns> {
-    chunk: StoredChunk,
-    ip: usize, // instruction pointer
-    debug_trace: bool,
-    value_stack: ValueStack,
-    globals: &'ns mut NameSpace,
+const FRAMES_MAX: usize = 1024;
+const MAIN_MODULE_NAME: &str = "main";
+
+/// Single function invocation, locals are addressed relative to ``base``
+struct CallFrame {
+    closure: ObjRef,
+    chunk: StoredChunk,       // chunk of the closure function
+    globals: StoredNameSpace, // globals of the closure module
+    ip: usize,                // instruction pointer
+    base: usize,
 }
 
-macro_rules! calc {
-    ($a:expr, $b:expr, $op:expr) => {{
-        match $op {
-            "+" => $a + $b,
-            "-" => $a - $b,
-            "*" => $a * $b,
-            "/" => $a / $b,
-            _ => panic!("Unsupported operator: {}", $op),
+impl CallFrame {
+    pub fn new(closure: ObjRef, base: usize) -> Self {
+        let as_closure = cast!(closure => ClosureObject).expect("Frame of non-closure object");
+        let function =
+            cast!(as_closure.function => FunctionObject).expect("Closure of non-function");
+        let module = cast!(as_closure.module => ModuleObject).expect("Closure of non-module");
+        Self {
+            closure,
+            chunk: function.chunk.clone(),
+            globals: module.globals.clone(),
+            ip: 0,
+            base,
         }
-    }};
+    }
+
+    pub fn chunk(&self) -> &StoredChunk {
+        &self.chunk
+    }
+
+    pub fn closure(&self) -> &ClosureObject {
+        cast!(self.closure => ClosureObject).unwrap()
+    }
+}
+
+/// Active ``try`` block, errors raised inside it continue at ``catch_ip``
+struct ExceptionHandler {
+    frames_count: usize,
+    stack_depth: usize,
+    catch_ip: usize,
 }
 
-impl<'ns> VirtualMachine<'ns> {
-    pub fn new(chunk: StoredChunk, globals: &'ns mut NameSpace, debug_trace: bool) -> Self {
+pub struct VirtualMachine {
+    frames: Vec<CallFrame>,
+    debug_trace: bool,
+    value_stack: ValueStack,
+    open_upvalues: Vec<StoredUpvalue>,
+    handlers: Vec<ExceptionHandler>,
+    thrown: Option<Value>, // value of the ``throw`` being unwound
+    builtins: NameSpace,   // natives visible from every module
+    modules: HashMap<PathBuf, ObjRef>,
+    importing: Vec<PathBuf>, // modules being executed, used to detect import cycles
+    heap: StoredHeap,
+    init_name: Symbol, // name of class initializers, interned once
+    output: Box<dyn Write>, // destination of ``print``
+}
+
+impl VirtualMachine {
+    pub fn new(
+        chunk: StoredChunk,
+        globals: StoredNameSpace,
+        heap: StoredHeap,
+        debug_trace: bool,
+    ) -> Self {
+        let script = {
+            let mut bheap = heap.borrow_mut();
+            let function = bheap.alloc(FunctionObject::with_chunk(None, chunk));
+            let module = bheap.alloc(ModuleObject::new(
+                Rc::new(MAIN_MODULE_NAME.to_owned()),
+                globals,
+            ));
+            bheap.alloc(ClosureObject::new(function, vec![], module))
+        };
+        let init_name = heap.borrow_mut().intern("init");
         Self {
-            chunk,
-            ip: 0,
+            frames: vec![CallFrame::new(script, 0)],
             debug_trace,
             value_stack: vec![],
-            globals,
+            open_upvalues: vec![],
+            handlers: vec![],
+            thrown: None,
+            builtins: NameSpace::new(),
+            modules: HashMap::new(),
+            importing: vec![],
+            heap,
+            init_name,
+            output: Box::new(io::stdout()),
         }
     }
 
+    /// Makes host function available to scripts as a global
+    pub fn define_native(&mut self, name: &str, arity: RangeInclusive<usize>, callback: NativeFn) {
+        let name = self.heap.borrow_mut().intern(name);
+        let native = self.allocate(NativeFunctionObject::new(name.clone(), arity, callback));
+        self.builtins.insert(name, native);
+    }
+
+    /// Redirects ``print`` statements, stdout by default
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    /// Globals of the main script, shared by every chunk compiled for it
+    pub fn main_globals(&self) -> StoredNameSpace {
+        self.frames[0].globals.clone()
+    }
+
+    pub fn heap(&self) -> StoredHeap {
+        self.heap.clone()
+    }
+
+    /// Moves object to the managed heap, collection never happens during an instruction,
+    /// so natives may hold the returned value without rooting it
+    pub fn allocate(&mut self, object: impl Object) -> Value {
+        Value::Object(self.heap.borrow_mut().alloc(object))
+    }
+
     pub fn exec(&mut self) -> VoidResult {
+        let result = self.run();
+        if result.is_err() {
+            self.reset();
+        }
+        result
+    }
+
+    /// Drops frames and values left by failed execution, so the script chunk can be reused
+    fn reset(&mut self) {
+        self.frames.truncate(1);
+        let script = &mut self.frames[0];
+        let script_len = script.chunk().borrow().len();
+        script.ip = script_len;
+        self.value_stack.clear();
+        self.open_upvalues.clear();
+        self.handlers.clear();
+        self.thrown = None;
+        self.importing.clear();
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("No call frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("No call frame")
+    }
+
+    fn globals(&self) -> &StoredNameSpace {
+        &self.frame().globals
+    }
+
+    /// Marks roots and frees unreachable objects. Frame closures keep chunk constants
+    /// and module globals alive, modules and builtins are reachable from the VM itself
+    fn collect_garbage(&mut self) {
+        let mut tracer = Tracer::new();
+        for slot in &self.value_stack {
+            slot.trace(&mut tracer);
+        }
+        for frame in &self.frames {
+            tracer.mark(frame.closure);
+        }
+        if let Some(thrown) = &self.thrown {
+            tracer.mark_value(thrown);
+        }
+        for module in self.modules.values() {
+            tracer.mark(*module);
+        }
+        self.builtins.trace(&mut tracer);
+
+        let mut bheap = self.heap.borrow_mut();
+        let freed = bheap.collect(tracer);
+        if self.debug_trace {
+            println!("GC: freed {freed} objects, {} alive", bheap.objects_count());
+        }
+    }
+
+    fn run(&mut self) -> VoidResult {
         if self.debug_trace {
+            let bchunk = self.frame().chunk().borrow();
             println!("Executing this chunk:");
-            println!("{}", self.chunk.borrow());
-            println!("Chunk constants: {:?}", self.chunk.borrow().constants);
+            println!("{bchunk}");
+            println!("Chunk constants: {:?}", bchunk.constants);
             println!()
         }
         loop {
-            let kind = {
-                let bchunk = self.chunk.borrow();
-                let Some(instruction) = bchunk.get(self.ip) else {
+            // instruction boundary is the only point where no unrooted handles exist
+            if self.heap.borrow().should_collect() {
+                self.collect_garbage();
+            }
+            let (kind, next_ip) = {
+                let frame = self.frame();
+                let Some(decoded) = frame.chunk().borrow().decode(frame.ip) else {
                     return Ok(());
                 };
-                instruction.kind().clone()
+                decoded
             };
+            self.frame_mut().ip = next_ip;
 
             if self.debug_trace {
                 println!("{kind}");
             }
 
-            match kind {
-                OpCodeKind::Const { const_idx } => {
-                    self.op_const(const_idx);
-                }
-                OpCodeKind::Negate => self.op_negate()?,
-                OpCodeKind::Add => self.bin_op(BinOpKind::Add)?,
-                OpCodeKind::Sub => self.bin_op(BinOpKind::Sub)?,
-                OpCodeKind::Mul => self.bin_op(BinOpKind::Mul)?,
-                OpCodeKind::Div => self.bin_op(BinOpKind::Div)?,
-                OpCodeKind::Null => {
-                    self.push_value(Value::Null);
-                }
-                OpCodeKind::True => {
-                    self.push_value(Value::Boolean(true));
-                }
-                OpCodeKind::False => {
-                    self.push_value(Value::Boolean(false));
-                }
-                OpCodeKind::Not => {
-                    let value = self.pop_or_err()?;
-                    self.push_value(Value::Boolean(!value.borrow().as_bool()));
-                }
-                OpCodeKind::Eq => self.op_cmp(Compare::Equal)?,
-                OpCodeKind::Gt => self.op_cmp(Compare::Greater)?,
-                OpCodeKind::Lt => self.op_cmp(Compare::Lower)?,
-                OpCodeKind::Print => self.op_print()?,
-                OpCodeKind::Pop => {
-                    self.pop_or_err()?;
+            if let Err(error) = self.execute(kind) {
+                self.handle_error(error)?;
+            }
+        }
+    }
+
+    fn execute(&mut self, kind: OpCodeKind) -> VoidResult {
+        match kind {
+            OpCodeKind::Const { const_idx } => {
+                self.op_const(const_idx);
+            }
+            OpCodeKind::Negate => self.op_negate()?,
+            OpCodeKind::Add => self.bin_op(BinOpKind::Add)?,
+            OpCodeKind::Sub => self.bin_op(BinOpKind::Sub)?,
+            OpCodeKind::Mul => self.bin_op(BinOpKind::Mul)?,
+            OpCodeKind::Div => self.bin_op(BinOpKind::Div)?,
+            OpCodeKind::Mod => self.bin_op(BinOpKind::Mod)?,
+            OpCodeKind::Pow => self.bin_op(BinOpKind::Pow)?,
+            OpCodeKind::FloorDiv => self.bin_op(BinOpKind::FloorDiv)?,
+            OpCodeKind::BitAnd => self.bin_op(BinOpKind::BitAnd)?,
+            OpCodeKind::BitOr => self.bin_op(BinOpKind::BitOr)?,
+            OpCodeKind::BitXor => self.bin_op(BinOpKind::BitXor)?,
+            OpCodeKind::ShiftLeft => self.bin_op(BinOpKind::ShiftLeft)?,
+            OpCodeKind::ShiftRight => self.bin_op(BinOpKind::ShiftRight)?,
+            OpCodeKind::BitNot => self.op_bit_not()?,
+            OpCodeKind::Null => {
+                self.push_value(Value::Null);
+            }
+            OpCodeKind::True => {
+                self.push_value(Value::Boolean(true));
+            }
+            OpCodeKind::False => {
+                self.push_value(Value::Boolean(false));
+            }
+            OpCodeKind::Not => {
+                let value = self.pop_or_err()?;
+                self.push_value(Value::Boolean(!value.as_bool()));
+            }
+            OpCodeKind::Eq => self.op_cmp(Compare::Equal)?,
+            OpCodeKind::Gt => self.op_cmp(Compare::Greater)?,
+            OpCodeKind::Lt => self.op_cmp(Compare::Lower)?,
+            OpCodeKind::Print => self.op_print()?,
+            OpCodeKind::Pop => {
+                self.pop_or_err()?;
+            }
+            OpCodeKind::DefineGlobal { slot } => self.op_define_global(slot, false)?,
+            OpCodeKind::DefineConst { slot } => self.op_define_global(slot, true)?,
+            OpCodeKind::ReadGlobal { slot } => self.op_read_global(slot)?,
+            OpCodeKind::SetGlobal { slot } => self.op_set_global(slot)?,
+            OpCodeKind::ReadLocal { name_idx } => self.op_read_local(name_idx)?,
+            OpCodeKind::SetLocal { name_idx } => self.op_set_local(name_idx)?,
+            OpCodeKind::JumpIfFalse { offset } => self.op_jump_if_false(offset)?,
+            OpCodeKind::Jump { offset } => self.op_jump(offset),
+            OpCodeKind::Loop { offset } => self.op_loop(offset),
+            OpCodeKind::Call { args_count } => self.op_call(args_count)?,
+            OpCodeKind::Return => self.op_return()?,
+            OpCodeKind::Closure { const_idx } => self.op_closure(const_idx)?,
+            OpCodeKind::ReadUpvalue { upvalue_idx } => self.op_read_upvalue(upvalue_idx),
+            OpCodeKind::SetUpvalue { upvalue_idx } => self.op_set_upvalue(upvalue_idx)?,
+            OpCodeKind::CloseUpvalue => {
+                self.close_upvalues(self.value_stack.len() - 1);
+                self.pop_or_err()?;
+            }
+            OpCodeKind::Class { name_idx } => {
+                let name = self.read_identifier_const(name_idx);
+                let class = self.allocate(ClassObject::new(name));
+                self.push_value(class);
+            }
+            OpCodeKind::Method { name_idx } => self.op_method(name_idx)?,
+            OpCodeKind::GetProperty { name_idx } => self.op_get_property(name_idx)?,
+            OpCodeKind::SetProperty { name_idx } => self.op_set_property(name_idx)?,
+            OpCodeKind::Inherit => self.op_inherit()?,
+            OpCodeKind::GetSuper { name_idx } => self.op_get_super(name_idx)?,
+            OpCodeKind::BuildList { items_count } => {
+                let items = self.pop_values(items_count);
+                let list = self.allocate(ListObject::new(items));
+                self.push_value(list);
+            }
+            OpCodeKind::GetIndex => self.op_get_index()?,
+            OpCodeKind::SetIndex => self.op_set_index()?,
+            OpCodeKind::BuildMap { entries_count } => self.op_build_map(entries_count)?,
+            OpCodeKind::Contains => self.op_contains()?,
+            OpCodeKind::BuildString { parts_count } => self.op_build_string(parts_count),
+            OpCodeKind::PushHandler { offset, depth } => {
+                let frame = self.frame();
+                self.handlers.push(ExceptionHandler {
+                    frames_count: self.frames.len(),
+                    stack_depth: frame.base + depth,
+                    catch_ip: frame.ip + offset,
+                });
+            }
+            OpCodeKind::PopHandler => {
+                self.handlers.pop().expect("No exception handler");
+            }
+            OpCodeKind::Throw => {
+                let value = self.pop_or_err()?;
+                return Err(self.throw(value));
+            }
+            OpCodeKind::GetIterator => self.op_get_iterator()?,
+            OpCodeKind::ForIter { slot, offset } => self.op_for_iter(slot, offset)?,
+            OpCodeKind::Import { path_idx } => self.op_import(path_idx)?,
+            OpCodeKind::Export { name_idx } => {
+                let name = self.read_identifier_const(name_idx);
+                self.globals().borrow_mut().export(name);
+            }
+            OpCodeKind::EndFinally => {
+                let should_rethrow = self.pop_or_err()?.as_bool();
+                let value = self.pop_or_err()?;
+                if should_rethrow {
+                    return Err(self.throw(value));
                 }
-                OpCodeKind::DefineGlobal { name_idx } => self.op_define_global(name_idx)?,
-                OpCodeKind::ReadGlobal { name_idx } => self.op_read_global(name_idx)?,
-                OpCodeKind::SetGlobal { name_idx } => self.op_set_global(name_idx)?,
-                OpCodeKind::ReadLocal { name_idx } => self.op_read_local(name_idx)?,
-                OpCodeKind::SetLocal { name_idx } => self.op_set_local(name_idx)?,
-                OpCodeKind::JumpIfFalse { offset } => self.op_jump_if_false(offset)?,
-                OpCodeKind::Jump { offset } => self.op_jump(offset),
-                OpCodeKind::Loop { offset } => self.op_loop(offset),
             }
+            OpCodeKind::Unwind { depth } => {
+                // value on top survives, locals above ``depth`` are dropped
+                let value = self.pop_or_err()?;
+                let stack_depth = self.frame().base + depth;
+                self.close_upvalues(stack_depth);
+                self.value_stack.truncate(stack_depth);
+                self.push_value(value);
+            }
+        }
+        Ok(())
+    }
 
-            if !matches!(kind, OpCodeKind::Loop { .. }) {
-                self.ip += 1;
+    fn op_get_iterator(&mut self) -> VoidResult {
+        let iterable = self.pop_or_err()?;
+        let iterator = match &iterable {
+            Value::Object(obj) => {
+                self.as_vm_result(obj.iterate(*obj, &mut self.heap.borrow_mut()))?
             }
+            other => {
+                return Err(self.runtime_error(RuntimeErrorKind::OperationNotSupported {
+                    target: format!("for {}", other.type_name()),
+                    op: "iteration".to_owned(),
+                }));
+            }
+        };
+        self.push_value(Value::Object(iterator));
+        Ok(())
+    }
+
+    /// Pushes next item of the iterator stored in local ``slot``, jumps out of the loop when exhausted
+    fn op_for_iter(&mut self, slot: usize, offset: usize) -> VoidResult {
+        let iterator = self.value_stack[self.frame().base + slot].unpack();
+        let next = match &iterator {
+            Value::Object(obj) => self.as_vm_result(obj.next_item(&mut self.heap.borrow_mut()))?,
+            _ => unreachable!(),
+        };
+        match next {
+            Some(value) => self.push_value(value),
+            None => self.frame_mut().ip += offset,
         }
+        Ok(())
     }
 
-    fn runtime_error(&self, kind: RuntimeErrorKind) -> Error {
-        let bchunk = self.chunk.borrow();
-        let Some(prev_instruction) = bchunk.get(self.ip - 1) else {
-            panic!("Cannot get previous instruction");
+    fn op_import(&mut self, path_idx: usize) -> VoidResult {
+        let import_path = self.read_identifier_const(path_idx);
+        let importer = self.globals().borrow().path().map(Path::to_path_buf);
+        let import_error = |reason: String| RuntimeErrorKind::ImportFailed {
+            path: import_path.to_string(),
+            reason,
+        };
+
+        let Some(path) = modules::resolve(&import_path, importer.as_deref()) else {
+            return Err(self.runtime_error(import_error("file is not found".to_owned())));
+        };
+        if let Some(module) = self.modules.get(&path) {
+            self.push_value(Value::Object(*module));
+            return Ok(());
+        }
+        if self.importing.contains(&path) {
+            return Err(self.runtime_error(import_error("circular import".to_owned())));
+        }
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(error) => return Err(self.runtime_error(import_error(error.to_string()))),
+        };
+        let chunk = rc_refcell!(Chunk::new());
+        let globals = rc_refcell!(NameSpace::from_file(path.clone()));
+        let mut compiler = Compiler::from_source(source, globals.clone(), self.heap.clone(), self.debug_trace);
+        if compiler.compile(chunk.clone()).is_err() {
+            return Err(self.runtime_error(import_error("module has syntax errors".to_owned())));
+        }
+
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let module = self
+            .heap
+            .borrow_mut()
+            .alloc(ModuleObject::new(Rc::new(name.into_owned()), globals));
+        self.exec_module(&path, chunk, module)?;
+
+        self.modules.insert(path, module);
+        self.push_value(Value::Object(module));
+        Ok(())
+    }
+
+    /// Runs module top-level code to completion. Handlers of the importer are hidden while it runs,
+    /// so module errors propagate to the import statement, where the importer can catch them
+    fn exec_module(&mut self, path: &Path, chunk: StoredChunk, module: ObjRef) -> VoidResult {
+        let script = {
+            let mut bheap = self.heap.borrow_mut();
+            let function = bheap.alloc(FunctionObject::with_chunk(None, chunk));
+            bheap.alloc(ClosureObject::new(function, vec![], module))
+        };
+        let base = self.value_stack.len();
+        let outer_handlers = std::mem::take(&mut self.handlers);
+        self.importing.push(path.to_path_buf());
+        self.frames.push(CallFrame::new(script, base));
+
+        let result = self.run();
+        self.importing.pop();
+        self.handlers = outer_handlers;
+        result?;
+
+        self.frames.pop();
+        self.value_stack.truncate(base);
+        Ok(())
+    }
+
+    /// Raises script value as error, runtime errors caught before keep their original line
+    fn throw(&mut self, value: Value) -> Error {
+        let error = match &value {
+            Value::Object(obj) if isinstance!(obj, ErrorObject) => {
+                let as_error = cast!(obj => ErrorObject).unwrap();
+                RuntimeError {
+                    kind: RuntimeErrorKind::Thrown {
+                        message: as_error.to_string(),
+                    },
+                    line: as_error.line,
+                    module: self.module_path(),
+                }
+                .into()
+            }
+            other => self.runtime_error(RuntimeErrorKind::Thrown {
+                message: format!("UncaughtError: {other}"),
+            }),
+        };
+        self.thrown = Some(value);
+        error
+    }
+
+    /// Unwinds to the innermost ``try`` block and jumps to its handler, propagates error if there is none
+    fn handle_error(&mut self, error: Error) -> VoidResult {
+        let Some(runtime_error) = error.downcast_ref::<RuntimeError>() else {
+            return Err(error);
+        };
+        let Some(handler) = self.handlers.pop() else {
+            return Err(error);
         };
 
+        let value = match &runtime_error.kind {
+            RuntimeErrorKind::Thrown { .. } => self.thrown.take().expect("No thrown value"),
+            kind => {
+                let error = ErrorObject::new(kind, runtime_error.line, &mut self.heap.borrow_mut());
+                self.allocate(error)
+            }
+        };
+        self.frames.truncate(handler.frames_count);
+        self.close_upvalues(handler.stack_depth);
+        self.value_stack.truncate(handler.stack_depth);
+        self.push_value(value);
+        self.frame_mut().ip = handler.catch_ip;
+        Ok(())
+    }
+
+    fn runtime_error(&self, kind: RuntimeErrorKind) -> Error {
+        let frame = self.frame();
+        // ip is already past the failed instruction, its last byte has the same line
+        let line = frame.chunk().borrow().line(frame.ip - 1);
+
         RuntimeError {
             kind,
-            line: prev_instruction.line(),
+            line,
+            module: self.module_path(),
         }
         .into()
     }
 
-    fn peek(&self) -> Result<StoredValue, Error> {
-        let Some(value) = self.value_stack.last().cloned() else {
+    /// File of the module running in the current frame, none for the main script
+    fn module_path(&self) -> Option<PathBuf> {
+        if Rc::ptr_eq(self.globals(), &self.frames[0].globals) {
+            return None;
+        }
+        self.globals().borrow().path().map(Path::to_path_buf)
+    }
+
+    fn peek(&self) -> Result<Value, Error> {
+        let Some(value) = self.value_stack.last() else {
             panic!("Missing stack value in peek()!");
         };
-        Ok(value)
+        Ok(value.unpack())
     }
 
-    fn push_value(&mut self, value: Value) {
-        self.value_stack.push(rc_refcell!(value));
+    /// Top of the stack in its stack representation, copied without unpacking
+    fn peek_slot(&self) -> Slot {
+        let Some(slot) = self.value_stack.last() else {
+            panic!("Missing stack value in peek_slot()!");
+        };
+        slot.clone()
     }
 
-    fn push_stored_value(&mut self, value: StoredValue) {
-        self.value_stack.push(value);
+    fn peek_at(&self, distance: usize) -> Value {
+        let Some(value) = self
+            .value_stack
+            .get(self.value_stack.len() - 1 - distance)
+        else {
+            panic!("Missing stack value in peek_at()!");
+        };
+        value.unpack()
     }
 
-    fn pop_or_err(&mut self) -> Result<StoredValue, Error> {
+    fn push_value(&mut self, value: Value) {
+        self.value_stack.push(Slot::pack(value, &self.heap));
+    }
+
+    fn pop_or_err(&mut self) -> Result<Value, Error> {
         let Some(value) = self.value_stack.pop() else {
             panic!("Missing stack value in pop()!");
         };
-        Ok(value)
+        Ok(value.unpack())
+    }
+
+    /// Pops ``count`` values keeping their order
+    fn pop_values(&mut self, count: usize) -> Vec<Value> {
+        let slots = self.value_stack.split_off(self.value_stack.len() - count);
+        slots.iter().map(Slot::unpack).collect()
     }
 
     fn as_vm_result<T>(&self, result: Result<T, RuntimeErrorKind>) -> Result<T, Error> {
@@ -155,14 +577,29 @@ impl<'ns> VirtualMachine<'ns> {
         let b = self.pop_or_err()?;
         let a = self.pop_or_err()?;
 
-        match (&*a.borrow(), &*b.borrow()) {
-            (Value::Float(a_val), Value::Float(b_val)) => {
-                let calculated = calc!(a_val, b_val, kind.to_string().as_str());
+        if kind.is_bitwise() {
+            let operands = integral_operand(&a).and_then(|a_val| {
+                integral_operand(&b).and_then(|b_val| kind.apply_bitwise(a_val, b_val))
+            });
+            let calculated = self.as_vm_result(operands)?;
+            self.push_value(calculated);
+            return Ok(());
+        }
+
+        match (&a, &b) {
+            (Value::Int(a_val), Value::Int(b_val)) => {
+                let calculated = self.as_vm_result(kind.apply_int(*a_val, *b_val))?;
+                self.push_value(calculated);
+            }
+            // mixed operands are promoted to float
+            (a_val @ (Value::Float(_) | Value::Int(_)), b_val @ (Value::Float(_) | Value::Int(_))) => {
+                let (a_val, b_val) = (a_val.as_float().unwrap(), b_val.as_float().unwrap());
+                let calculated = self.as_vm_result(kind.apply_float(a_val, b_val))?;
                 self.push_value(Value::Float(calculated));
             }
-            (Value::Object(a), Value::Object(b)) => {
-                let result = self.as_vm_result(a.add(b))?;
-                self.push_stored_value(result);
+            (Value::Object(a), Value::Object(b)) if matches!(kind, BinOpKind::Add) => {
+                let result = self.as_vm_result(a.add(*b, &mut self.heap.borrow_mut()))?;
+                self.push_value(result);
             }
             (val1, val2) => {
                 return Err(self.runtime_error(RuntimeErrorKind::OperationNotSupported {
@@ -175,11 +612,11 @@ impl<'ns> VirtualMachine<'ns> {
         Ok(())
     }
 
-    fn read_identifier_const(&self, idx: usize) -> Rc<String> {
-        let bchunk = self.chunk.borrow();
+    fn read_identifier_const(&self, idx: usize) -> Symbol {
+        let bchunk = self.frame().chunk().borrow();
         let const_value = bchunk.get_const(idx).unwrap();
 
-        match &*const_value.borrow() {
+        match const_value {
             Value::Identifier(identifier) => identifier.clone(),
             _ => unreachable!(),
         }
@@ -187,27 +624,42 @@ impl<'ns> VirtualMachine<'ns> {
 
     fn op_const(&mut self, const_idx: usize) {
         let cloned_value = {
-            let bchunk = self.chunk.borrow();
+            let bchunk = self.frame().chunk().borrow();
             let const_value = bchunk.get_const(const_idx).unwrap();
             if self.debug_trace {
-                println!("Pushed const: {}", const_value.borrow());
+                println!("Pushed const: {const_value}");
             }
             const_value.clone()
         };
-        self.push_stored_value(cloned_value);
+        self.push_value(cloned_value);
+    }
+
+    fn op_bit_not(&mut self) -> VoidResult {
+        let value = self.pop_or_err()?;
+        let operand = self.as_vm_result(integral_operand(&value))?;
+        self.push_value(Value::Int(!operand));
+        Ok(())
     }
 
     fn op_negate(&mut self) -> VoidResult {
         let peek = self.peek()?;
-        if !peek.borrow().support_negation() {
+        if !peek.support_negation() {
             return Err(self.runtime_error(RuntimeErrorKind::OperationNotSupported {
                 op: "-".to_owned(),
-                target: format!("for {}", peek.borrow()),
+                target: format!("for {peek}"),
             }));
         }
 
         let value = self.pop_or_err()?;
-        match &*value.borrow() {
+        match &value {
+            Value::Int(int_value) => {
+                let Some(negated) = int_value.checked_neg() else {
+                    return Err(self.runtime_error(RuntimeErrorKind::IntegerOverflow {
+                        expression: format!("-({int_value})"),
+                    }));
+                };
+                self.push_value(Value::Int(negated));
+            }
             Value::Float(float_value) => {
                 self.push_value(Value::Float(-float_value));
             }
@@ -220,7 +672,7 @@ impl<'ns> VirtualMachine<'ns> {
         let b = self.pop_or_err()?;
         let a = self.pop_or_err()?;
 
-        let cmp_result = a.borrow().cmp(&b.borrow());
+        let cmp_result = a.cmp(&b);
         if cmp_result.is_err() {
             #[allow(clippy::unnecessary_unwrap)]
             return Err(self.runtime_error(cmp_result.unwrap_err()));
@@ -232,75 +684,460 @@ impl<'ns> VirtualMachine<'ns> {
 
     fn op_print(&mut self) -> VoidResult {
         let value = self.pop_or_err()?;
-        println!("{}", value.borrow());
+        writeln!(self.output, "{value}")?;
         Ok(())
     }
 
-    fn op_define_global(&mut self, name_idx: usize) -> VoidResult {
-        let name = self.read_identifier_const(name_idx);
+    fn global_name(&self, slot: usize) -> String {
+        self.globals().borrow().name(slot).to_string()
+    }
 
-        if self.globals.get(&name).is_some() {
+    fn op_define_global(&mut self, slot: usize, is_const: bool) -> VoidResult {
+        if self.globals().borrow().is_defined(slot) {
             return Err(
                 self.runtime_error(RuntimeErrorKind::AlreadyDefinedVariable {
-                    name: name.to_string(),
+                    name: self.global_name(slot),
                 }),
             );
         }
-        self.globals.insert(name, self.peek()?);
-        self.pop_or_err()?;
+        let value = self.pop_or_err()?;
+        self.globals().borrow_mut().define(slot, value, is_const);
         Ok(())
     }
 
-    fn op_read_global(&mut self, name_idx: usize) -> VoidResult {
-        let name = self.read_identifier_const(name_idx);
-        let Some(value) = self.globals.get(&name) else {
+    fn op_read_global(&mut self, slot: usize) -> VoidResult {
+        let value = self.globals().borrow().read(slot);
+        // natives are looked up by name only when the module does not define the global
+        let value = value.or_else(|| self.builtins.get(self.globals().borrow().name(slot)));
+        let Some(value) = value else {
             return Err(self.runtime_error(RuntimeErrorKind::UndefinedVariable {
-                name: name.to_string(),
+                name: self.global_name(slot),
             }));
         };
-        self.push_stored_value(value);
+        self.push_value(value);
         Ok(())
     }
 
-    fn op_set_global(&mut self, name_idx: usize) -> VoidResult {
-        let name = self.read_identifier_const(name_idx);
-
-        let Some(_) = self.globals.get(&name) else {
+    fn op_set_global(&mut self, slot: usize) -> VoidResult {
+        if !self.globals().borrow().is_defined(slot) {
             return Err(self.runtime_error(RuntimeErrorKind::UndefinedVariable {
-                name: name.to_string(),
+                name: self.global_name(slot),
             }));
         };
+        if self.globals().borrow().is_const(slot) {
+            return Err(self.runtime_error(RuntimeErrorKind::ConstantAssignment {
+                name: self.global_name(slot),
+            }));
+        }
 
-        self.globals.insert(name, self.peek()?);
+        let value = self.peek()?;
+        self.globals().borrow_mut().set(slot, value);
         Ok(())
     }
 
     fn op_read_local(&mut self, name_idx: usize) -> VoidResult {
-        let Some(value) = self.value_stack.get(name_idx) else {
+        let slot = self.frame().base + name_idx;
+        let Some(value) = self.value_stack.get(slot) else {
             panic!("Missing stack value in read local!");
         };
+        // slots are copied as is, without unpacking
         let cloned_value = value.clone();
-        self.push_stored_value(cloned_value);
+        self.value_stack.push(cloned_value);
         Ok(())
     }
 
     fn op_set_local(&mut self, name_idx: usize) -> VoidResult {
-        self.value_stack[name_idx] = self.peek()?;
+        let slot = self.frame().base + name_idx;
+        self.value_stack[slot] = self.peek_slot();
         Ok(())
     }
 
     fn op_jump_if_false(&mut self, offset: usize) -> VoidResult {
-        if !self.peek()?.borrow().as_bool() {
-            self.ip += offset;
+        if !self.peek()?.as_bool() {
+            self.frame_mut().ip += offset;
         }
         Ok(())
     }
 
     fn op_jump(&mut self, offset: usize) {
-        self.ip += offset;
+        self.frame_mut().ip += offset;
     }
 
     fn op_loop(&mut self, offset: usize) {
-        self.ip -= offset;
+        self.frame_mut().ip -= offset;
+    }
+
+    fn op_call(&mut self, args_count: usize) -> VoidResult {
+        let callee = self.peek_at(args_count);
+        self.call_value(callee, args_count)
+    }
+
+    fn call_value(&mut self, callee: Value, args_count: usize) -> VoidResult {
+        let Value::Object(obj) = callee else {
+            return Err(self.runtime_error(RuntimeErrorKind::NotCallable {
+                type_name: callee.type_name(),
+            }));
+        };
+
+        if isinstance!(obj, ClosureObject) {
+            return self.call(obj, args_count);
+        }
+
+        if isinstance!(obj, NativeFunctionObject) {
+            let native = self.as_vm_result(cast!(obj => NativeFunctionObject))?;
+            return self.call_native(native, args_count);
+        }
+
+        if isinstance!(obj, BoundMethodObject) {
+            let bound = self.as_vm_result(cast!(obj => BoundMethodObject))?;
+            let base = self.value_stack.len() - args_count - 1;
+            self.value_stack[base] = Slot::pack(bound.receiver.clone(), &self.heap);
+            return self.call(bound.method, args_count);
+        }
+
+        if isinstance!(obj, ClassObject) {
+            let class = self.as_vm_result(cast!(obj => ClassObject))?;
+            let base = self.value_stack.len() - args_count - 1;
+            let instance = self.allocate(InstanceObject::new(obj));
+            self.value_stack[base] = Slot::pack(instance, &self.heap);
+
+            if let Some(initializer) = class.find_method(&self.init_name) {
+                return self.call(initializer, args_count);
+            }
+            if args_count != 0 {
+                return Err(self.runtime_error(RuntimeErrorKind::ArgumentsCount {
+                    name: class.to_string(),
+                    expected: 0,
+                    provided: args_count,
+                }));
+            }
+            return Ok(());
+        }
+
+        Err(self.runtime_error(RuntimeErrorKind::NotCallable {
+            type_name: obj.type_name(),
+        }))
+    }
+
+    fn call(&mut self, closure: ObjRef, args_count: usize) -> VoidResult {
+        let as_closure = self.as_vm_result(cast!(closure => ClosureObject))?;
+        let function = self.as_vm_result(cast!(as_closure.function => FunctionObject))?;
+        if function.arity != args_count {
+            return Err(self.runtime_error(RuntimeErrorKind::ArgumentsCount {
+                name: function.to_string(),
+                expected: function.arity,
+                provided: args_count,
+            }));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.runtime_error(RuntimeErrorKind::StackOverflow {
+                max_depth: FRAMES_MAX,
+            }));
+        }
+
+        let base = self.value_stack.len() - args_count - 1;
+        self.frames.push(CallFrame::new(closure, base));
+        Ok(())
+    }
+
+    fn call_native(&mut self, native: &NativeFunctionObject, args_count: usize) -> VoidResult {
+        if !native.arity.contains(&args_count) {
+            let (name, min, max) = (native.to_string(), *native.arity.start(), *native.arity.end());
+            let kind = if min == max {
+                RuntimeErrorKind::ArgumentsCount { name, expected: min, provided: args_count }
+            } else {
+                RuntimeErrorKind::ArgumentsCountRange { name, min, max, provided: args_count }
+            };
+            return Err(self.runtime_error(kind));
+        }
+
+        let args_start = self.value_stack.len() - args_count;
+        let args: Vec<Value> = self.value_stack[args_start..]
+            .iter()
+            .map(Slot::unpack)
+            .collect();
+        let result = (native.callback)(self, &args);
+        let result = self.as_vm_result(result)?;
+
+        // dropping the callee together with its arguments
+        self.value_stack.truncate(args_start - 1);
+        self.push_value(result);
+        Ok(())
+    }
+
+    fn op_return(&mut self) -> VoidResult {
+        let result = self.pop_or_err()?;
+        let frame = self.frames.pop().expect("No call frame");
+        self.close_upvalues(frame.base);
+        self.value_stack.truncate(frame.base);
+        // return from inside of try block leaves its handlers behind
+        while self
+            .handlers
+            .last()
+            .is_some_and(|handler| handler.frames_count > self.frames.len())
+        {
+            self.handlers.pop();
+        }
+        self.push_value(result);
+        Ok(())
+    }
+
+    fn op_closure(&mut self, const_idx: usize) -> VoidResult {
+        let function = {
+            let bchunk = self.frame().chunk().borrow();
+            let Value::Object(obj) = bchunk.get_const(const_idx).unwrap() else {
+                unreachable!()
+            };
+            *obj
+        };
+        let as_function = self.as_vm_result(cast!(function => FunctionObject))?;
+
+        let upvalues = as_function
+            .upvalues
+            .iter()
+            .map(|descriptor| {
+                if descriptor.is_local {
+                    self.capture_upvalue(self.frame().base + descriptor.index)
+                } else {
+                    self.frame().closure().upvalues[descriptor.index].clone()
+                }
+            })
+            .collect();
+
+        let module = self.frame().closure().module;
+        let closure = self.allocate(ClosureObject::new(function, upvalues, module));
+        self.push_value(closure);
+        Ok(())
+    }
+
+    fn op_method(&mut self, name_idx: usize) -> VoidResult {
+        let name = self.read_identifier_const(name_idx);
+        let method = self.pop_or_err()?;
+        let class = self.peek()?;
+
+        let (Value::Object(method_obj), Value::Object(class_obj)) = (method, class) else {
+            unreachable!()
+        };
+        self.as_vm_result(cast!(method_obj => ClosureObject))?;
+        let class = self.as_vm_result(cast!(class_obj => ClassObject))?;
+        class.add_method(name, method_obj);
+        Ok(())
+    }
+
+    fn op_get_property(&mut self, name_idx: usize) -> VoidResult {
+        let name = self.read_identifier_const(name_idx);
+        let target = self.pop_or_err()?;
+
+        let Value::Object(obj) = target else {
+            return Err(self.runtime_error(RuntimeErrorKind::UndefinedAttribute {
+                type_name: target.type_name(),
+                name: name.to_string(),
+            }));
+        };
+        let property = match obj.get_attribute(&name) {
+            Some(value) => Some(value),
+            None if isinstance!(obj, InstanceObject) => {
+                let instance = self.as_vm_result(cast!(obj => InstanceObject))?;
+                instance
+                    .class()
+                    .find_method(&name)
+                    .map(|method| self.allocate(BoundMethodObject::new(target.clone(), method)))
+            }
+            None => None,
+        };
+
+        let Some(property) = property else {
+            return Err(self.runtime_error(RuntimeErrorKind::UndefinedAttribute {
+                type_name: target.type_name(),
+                name: name.to_string(),
+            }));
+        };
+        self.push_value(property);
+        Ok(())
+    }
+
+    fn op_set_property(&mut self, name_idx: usize) -> VoidResult {
+        let name = self.read_identifier_const(name_idx);
+        let value = self.pop_or_err()?;
+        let target = self.pop_or_err()?;
+
+        let result = match &target {
+            Value::Object(obj) => obj.set_attribute(&name, value.clone()),
+            other => Err(RuntimeErrorKind::OperationNotSupported {
+                target: format!("for {}", other.type_name()),
+                op: "attribute assignment".to_owned(),
+            }),
+        };
+        self.as_vm_result(result)?;
+        self.push_value(value);
+        Ok(())
+    }
+
+    fn op_inherit(&mut self) -> VoidResult {
+        let subclass = self.pop_or_err()?;
+        let superclass = self.peek()?;
+
+        let Value::Object(superclass_obj) = superclass else {
+            return Err(self.runtime_error(RuntimeErrorKind::InvalidSuperclass {
+                type_name: superclass.type_name(),
+            }));
+        };
+        if !isinstance!(superclass_obj, ClassObject) {
+            return Err(self.runtime_error(RuntimeErrorKind::InvalidSuperclass {
+                type_name: superclass_obj.type_name(),
+            }));
+        }
+        let superclass = self.as_vm_result(cast!(superclass_obj => ClassObject))?;
+
+        let Value::Object(subclass_obj) = subclass else {
+            unreachable!()
+        };
+        let subclass = self.as_vm_result(cast!(subclass_obj => ClassObject))?;
+
+        // methods are copied down, so overrides defined later replace them
+        for (name, method) in superclass.methods.borrow().iter() {
+            subclass.add_method(name.clone(), *method);
+        }
+        Ok(())
+    }
+
+    fn op_get_super(&mut self, name_idx: usize) -> VoidResult {
+        let name = self.read_identifier_const(name_idx);
+        let superclass = self.pop_or_err()?;
+        let instance = self.pop_or_err()?;
+
+        let Value::Object(superclass_obj) = superclass else {
+            unreachable!()
+        };
+        let superclass = self.as_vm_result(cast!(superclass_obj => ClassObject))?;
+        let Some(method) = superclass.find_method(&name) else {
+            return Err(self.runtime_error(RuntimeErrorKind::UndefinedSuperMethod {
+                class_name: superclass.name.to_string(),
+                name: name.to_string(),
+            }));
+        };
+        let bound = self.allocate(BoundMethodObject::new(instance, method));
+        self.push_value(bound);
+        Ok(())
+    }
+
+    fn op_build_map(&mut self, entries_count: usize) -> VoidResult {
+        let flat_entries = self.pop_values(entries_count * 2);
+
+        let map = MapObject::new();
+        for pair in flat_entries.chunks(2) {
+            let result = map.entries.borrow_mut().insert(pair[0].clone(), pair[1].clone());
+            self.as_vm_result(result)?;
+        }
+        let map = self.allocate(map);
+        self.push_value(map);
+        Ok(())
+    }
+
+    fn op_build_string(&mut self, parts_count: usize) {
+        let parts = self.pop_values(parts_count);
+
+        let mut built = String::new();
+        for part in parts {
+            built.push_str(&part.to_string());
+        }
+        let string = Value::Object(self.heap.borrow_mut().alloc_string(&built));
+        self.push_value(string);
+    }
+
+    fn op_contains(&mut self) -> VoidResult {
+        let container = self.pop_or_err()?;
+        let item = self.pop_or_err()?;
+
+        let result = match &container {
+            Value::Object(obj) => obj.contains(&item),
+            other => Err(RuntimeErrorKind::OperationNotSupported {
+                target: format!("for {}", other.type_name()),
+                op: "in".to_owned(),
+            }),
+        };
+        let contains = self.as_vm_result(result)?;
+        self.push_value(Value::Boolean(contains));
+        Ok(())
+    }
+
+    fn op_get_index(&mut self) -> VoidResult {
+        let index = self.pop_or_err()?;
+        let target = self.pop_or_err()?;
+
+        let result = match &target {
+            Value::Object(obj) => obj.get_item(&index),
+            other => Err(RuntimeErrorKind::OperationNotSupported {
+                target: format!("for {}", other.type_name()),
+                op: "indexing".to_owned(),
+            }),
+        };
+        let item = self.as_vm_result(result)?;
+        self.push_value(item);
+        Ok(())
+    }
+
+    fn op_set_index(&mut self) -> VoidResult {
+        let value = self.pop_or_err()?;
+        let index = self.pop_or_err()?;
+        let target = self.pop_or_err()?;
+
+        let result = match &target {
+            Value::Object(obj) => obj.set_item(&index, value.clone()),
+            other => Err(RuntimeErrorKind::OperationNotSupported {
+                target: format!("for {}", other.type_name()),
+                op: "item assignment".to_owned(),
+            }),
+        };
+        self.as_vm_result(result)?;
+        self.push_value(value);
+        Ok(())
+    }
+
+    /// Returns upvalue pointing to the stack slot, reusing already captured one
+    fn capture_upvalue(&mut self, slot: usize) -> StoredUpvalue {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(idx) if idx == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+
+        let upvalue = rc_refcell!(Upvalue::Open(slot));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves values of stack slots starting from ``from_slot`` into their upvalues
+    fn close_upvalues(&mut self, from_slot: usize) {
+        let value_stack = &self.value_stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut bupvalue = upvalue.borrow_mut();
+            match *bupvalue {
+                Upvalue::Open(slot) if slot >= from_slot => {
+                    *bupvalue = Upvalue::Closed(value_stack[slot].unpack());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn op_read_upvalue(&mut self, upvalue_idx: usize) {
+        let upvalue = self.frame().closure().upvalues[upvalue_idx].clone();
+        match &*upvalue.borrow() {
+            Upvalue::Open(slot) => self.value_stack.push(self.value_stack[*slot].clone()),
+            Upvalue::Closed(value) => self.push_value(value.clone()),
+        };
+    }
+
+    fn op_set_upvalue(&mut self, upvalue_idx: usize) -> VoidResult {
+        let upvalue = self.frame().closure().upvalues[upvalue_idx].clone();
+        match &mut *upvalue.borrow_mut() {
+            Upvalue::Open(slot) => self.value_stack[*slot] = self.peek_slot(),
+            Upvalue::Closed(closed) => *closed = self.peek()?,
+        };
+        Ok(())
     }
 }